@@ -1,4 +1,5 @@
 import { theme } from "$lib/theme.svelte";
+import type { BoardSize } from "$lib/types";
 
 export const BOARD_WIDTH = 10;
 export const BOARD_HEIGHT = 20;
@@ -71,7 +72,7 @@ export function displayCell(ctx: CanvasRenderingContext2D, col: number, row: num
     ctx.stroke();
 }
 
-function displayGrid(ctx: CanvasRenderingContext2D) {
+function displayGrid(ctx: CanvasRenderingContext2D, cols: number, rows: number) {
     ctx.lineWidth = 1;
     if (theme.prefersDark) {
         ctx.strokeStyle = "#333";
@@ -79,16 +80,16 @@ function displayGrid(ctx: CanvasRenderingContext2D) {
         ctx.strokeStyle = "#aaa";
     }
     // draw lines
-    const width = BOARD_WIDTH * CELL_SIZE;
-    const height = BOARD_HEIGHT * CELL_SIZE;
-    for (let r = 0; r <= BOARD_HEIGHT; r++) {
+    const width = cols * CELL_SIZE;
+    const height = rows * CELL_SIZE;
+    for (let r = 0; r <= rows; r++) {
         const y = r * CELL_SIZE;
         ctx.beginPath();
         ctx.moveTo(0, y + 0.5);
         ctx.lineTo(width, y + 0.5);
         ctx.stroke();
     }
-    for (let c = 0; c <= BOARD_WIDTH; c++) {
+    for (let c = 0; c <= cols; c++) {
         const x = c * CELL_SIZE;
         ctx.beginPath();
         ctx.moveTo(x + 0.5, 0);
@@ -192,19 +193,30 @@ export function crossOutCell(ctx: CanvasRenderingContext2D, col: number, row: nu
     ctx.stroke();
 }
 
+// Sizes the canvas to the visible rows of a board, resizing clears the canvas
+export function resizeBoard(canvas: HTMLCanvasElement, size: BoardSize) {
+    const width = size.width * CELL_SIZE;
+    const height = (size.height - size.hidden) * CELL_SIZE;
+    if (canvas.width !== width || canvas.height !== height) {
+        canvas.width = width;
+        canvas.height = height;
+    }
+}
+
 export function clearBoard(ctx: CanvasRenderingContext2D, canvas: HTMLCanvasElement) {
     ctx.clearRect(0, 0, canvas.width, canvas.height);
-    displayGrid(ctx);
+    displayGrid(ctx, canvas.width / CELL_SIZE, canvas.height / CELL_SIZE);
 }
 
-export function displayBoard(ctx: CanvasRenderingContext2D, board: BoardData) {
-    for (let row = 0; row < BOARD_HEIGHT; row++) {
-        for (let col = 0; col < BOARD_WIDTH; col++) {
+// The top `hidden` rows of the board are not displayed
+export function displayBoard(ctx: CanvasRenderingContext2D, board: BoardData, hidden: number = 0) {
+    for (let row = hidden; row < board.length; row++) {
+        for (let col = 0; col < board[row].length; col++) {
             const cellValue = board[row][col];
             if (cellValue === 0) {
                 continue;
             }
-            displayCell(ctx, col, row, cellValue - 1);
+            displayCell(ctx, col, row - hidden, cellValue - 1);
         }
     }
 }
@@ -92,6 +92,7 @@ Eerst ga je zelf Tetris spelen om een gevoel te krijgen voor het spel.</p>`,
                 the left/right
             </li>
             <li><strong>Up arrow/Z</strong>: Rotate the tetromino</li>
+            <li><strong>X</strong>: Rotate the tetromino counter-clockwise</li>
             <li><strong>Down arrow</strong>: Move the tetromino down (soft drop)</li>
             <li><strong>Space</strong>: Drop the tetromino (hard drop)</li>
         </ul>`,
@@ -100,6 +101,7 @@ Eerst ga je zelf Tetris spelen om een gevoel te krijgen voor het spel.</p>`,
  <li>
  <strong>Pijltjes links/rechts</strong>: verplaats de tetromino naar links/rechts</li>
  <li><strong>Pijltje omhoog/Z</strong>: draai de tetromino</li>
+ <li><strong>X</strong>: draai de tetromino tegen de klok in</li>
  <li><strong>Pijltje omlaag</strong>: verplaats de tetromino naar beneden</li>
  <li><strong>Spatiebalk</strong>: laat de tetromino vallen</li>
  </ul>`
@@ -252,7 +254,7 @@ export const LEVEL_CONFIG: { [key: string]: LevelConfig } = {
         },
         features: [
             ["pits", 0],
-            ["landing_height", 0]
+            ["padded_landing_height", 0]
         ]
     },
     "feat2": {
@@ -270,13 +272,13 @@ export const LEVEL_CONFIG: { [key: string]: LevelConfig } = {
         },
         features: [
             ["pits", -10],
-            ["landing_height", -4],
+            ["padded_landing_height", -4],
             ["eroded_cells", 0],
             ["cuml_wells", 0],
         ],
         lockedFeatures: [
             "pits",
-            "landing_height"
+            "padded_landing_height"
         ]
     },
     "feat4": {
@@ -287,7 +289,7 @@ export const LEVEL_CONFIG: { [key: string]: LevelConfig } = {
             ["col_trans", -10],
             ["row_trans", -4],
             ["pits", -5],
-            ["landing_height", -3],
+            ["padded_landing_height", -3],
             ["eroded_cells", 0],
             ["cuml_wells", 0]
         ],
@@ -295,7 +297,7 @@ export const LEVEL_CONFIG: { [key: string]: LevelConfig } = {
             "col_trans",
             "row_trans",
             "pits",
-            "landing_height"
+            "padded_landing_height"
         ],
     }
 };
@@ -5,8 +5,10 @@ export class TetrisSimulator {
     #simulator: Simulator = new Simulator();
     #state: TetrisState = $state({
         board: this.#simulator.state.board,
+        size: this.#simulator.state.size,
         stats: this.#simulator.state.stats,
         gameOver: false,
+        next: [],
     });
     #next: TetrisState = this.#simulator.state;
     #path: Path | null = null;
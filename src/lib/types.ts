@@ -1,14 +1,49 @@
 export type TetrisState = {
     board: Uint8Array[];
+    size: BoardSize;
     stats: Stats;
     gameOver: boolean;
+    next: number[];
+    hold?: number;
 }
 
+export type BoardSize = {
+    width: number;
+    height: number;
+    hidden: number;
+};
+
 export type Stats = {
     steps: bigint;
     lines: bigint;
     score: bigint;
     level: bigint;
     tetrises: bigint;
+    tspins: bigint;
+    mini_tspins: bigint;
+    combo: bigint;
+    max_combo: bigint;
+    back_to_back: bigint;
+    perfect_clears: bigint;
+};
+
+
+export type FeatureContribution = {
+    name: string;
+    value: number;
+    weight: number;
+    contribution: number;
+};
+
+export type Candidate = {
+    move: { piece: number; pos: { rot: number; row: number; col: number } };
+    features: FeatureContribution[];
+    evaluation: number;
+    score?: number;
 };
 
+export type Explanation = {
+    candidates: Candidate[];
+    chosen?: number;
+    rank: number;
+};
@@ -1,6 +1,6 @@
 import init, { Trainer } from "tetris-ai";
 
-export type TrainCriterion = "score" | "level" | "tetrises";
+export type TrainCriterion = "score" | "level" | "tetrises" | "perfect_clears";
 
 export type WorkerCommand =
     | { command: 'restart', featureNames: string[], criterion: TrainCriterion }
@@ -1,4 +1,5 @@
 use crate::{r#move::Move, piece::Pattern};
+use serde::Serialize;
 use std::{fmt::Display, ops::Index, str::FromStr};
 
 /// Represents a cell on the board.
@@ -43,169 +44,316 @@ impl Display for Cell {
     }
 }
 
+/// The width of a standard board.
 pub const BOARD_WIDTH: usize = 10;
+/// The height of a standard board.
 pub const BOARD_HEIGHT: usize = 20;
+/// The maximum width of a board, as each row is stored in a `u16`.
+pub const MAX_BOARD_WIDTH: usize = 16;
+/// The maximum height of a board, including the hidden rows.
+pub const MAX_BOARD_HEIGHT: usize = 40;
+/// The minimum width and height of a board, so that every piece fits.
+const MIN_BOARD_SIZE: usize = 4;
+
+/// The dimensions of a board.
+/// The top `hidden` rows are a buffer above the visible rows where the pieces spawn,
+/// like the 10x40 boards of modern guideline games.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct BoardSize {
+    pub width: usize,
+    /// The total number of rows, including the hidden rows.
+    pub height: usize,
+    pub hidden: usize,
+}
+
+impl Default for BoardSize {
+    fn default() -> Self {
+        Self {
+            width: BOARD_WIDTH,
+            height: BOARD_HEIGHT,
+            hidden: 0,
+        }
+    }
+}
+
+impl BoardSize {
+    /// Creates a board size with `height` rows in total, of which the top `hidden` rows are hidden.
+    /// Returns `None` if the board is too small to fit every piece or too large to be stored.
+    pub fn new(width: usize, height: usize, hidden: usize) -> Option<Self> {
+        ((MIN_BOARD_SIZE..=MAX_BOARD_WIDTH).contains(&width)
+            && (MIN_BOARD_SIZE..=MAX_BOARD_HEIGHT).contains(&height)
+            && height
+                .checked_sub(hidden)
+                .is_some_and(|visible| visible >= MIN_BOARD_SIZE))
+        .then_some(Self {
+            width,
+            height,
+            hidden,
+        })
+    }
+
+    /// Returns the number of rows that are not hidden.
+    pub fn visible(&self) -> usize {
+        self.height - self.hidden
+    }
+}
+
+impl FromStr for BoardSize {
+    type Err = ();
+
+    /// Parses a board size as `<width>x<visible rows>`, optionally followed by `+<hidden rows>`,
+    /// e.g. `10x20`, `4x20` or `10x20+20`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (width, rows) = s.split_once('x').ok_or(())?;
+        let (visible, hidden) = rows.split_once('+').unwrap_or((rows, "0"));
+        let width = width.parse().map_err(|_| ())?;
+        let visible: usize = visible.parse().map_err(|_| ())?;
+        let hidden: usize = hidden.parse().map_err(|_| ())?;
+        let height = visible.checked_add(hidden).ok_or(())?;
+        BoardSize::new(width, height, hidden).ok_or(())
+    }
+}
+
+/// The rows cleared by [`Board::clear_full`], stored as a bitmask of the row indices before clearing.
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
+pub struct ClearedRows(u64);
+
+impl ClearedRows {
+    /// Returns the number of cleared rows.
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns the indices of the cleared rows, from bottom to top.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + use<> {
+        let mut bits = self.0;
+        std::iter::from_fn(move || {
+            if bits == 0 {
+                return None;
+            }
+            let row = 63 - bits.leading_zeros() as usize;
+            bits &= !(1 << row);
+            Some(row)
+        })
+    }
+}
 
 /// Represents a Tetris board.
-#[derive(Copy, Clone, Default, Debug)]
+/// Each row is stored as a bitmask where bit `c` is set if column `c` is filled,
+/// the colors of the cells are only kept for rendering.
+/// The storage always fits the largest board, only the first rows and columns are used.
+#[derive(Copy, Clone, Debug)]
 pub struct Board {
-    data: [[Cell; BOARD_WIDTH]; BOARD_HEIGHT],
-    heights: [usize; BOARD_WIDTH],
+    size: BoardSize,
+    full_row: u16,
+    rows: [u16; MAX_BOARD_HEIGHT],
+    data: [[Cell; MAX_BOARD_WIDTH]; MAX_BOARD_HEIGHT],
+    heights: [usize; MAX_BOARD_WIDTH],
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self::new(BoardSize::default())
+    }
 }
 
 impl Board {
-    #[allow(clippy::needless_range_loop)]
-    pub(crate) fn from_data(data: [[Cell; BOARD_WIDTH]; BOARD_HEIGHT]) -> Self {
-        let mut heights = [0; BOARD_WIDTH];
-        for c in 0..BOARD_WIDTH {
-            for r in 0..BOARD_HEIGHT {
-                if data[r][c].filled() {
-                    heights[c] = BOARD_HEIGHT - r;
-                    break;
-                }
-            }
+    /// Creates an empty board of the given size.
+    pub fn new(size: BoardSize) -> Self {
+        Self {
+            size,
+            full_row: ((1u32 << size.width) - 1) as u16,
+            rows: [0; MAX_BOARD_HEIGHT],
+            data: [[Cell::default(); MAX_BOARD_WIDTH]; MAX_BOARD_HEIGHT],
+            heights: [0; MAX_BOARD_WIDTH],
         }
-        Board { data, heights }
+    }
+
+    pub fn size(&self) -> BoardSize {
+        self.size
+    }
+
+    pub fn width(&self) -> usize {
+        self.size.width
     }
 
     /// Returns the height of the given column.
-    pub(crate) fn height(&self, col: usize) -> usize {
+    pub fn height(&self, col: usize) -> usize {
         self.heights[col]
     }
 
-    pub(crate) fn heights(&self) -> &[usize; BOARD_WIDTH] {
-        &self.heights
+    /// Returns the heights of the columns, from left to right.
+    pub(crate) fn heights(&self) -> &[usize] {
+        &self.heights[..self.size.width]
+    }
+
+    /// Returns the bitmasks of the rows, from top to bottom.
+    #[inline]
+    pub(crate) fn rows(&self) -> &[u16] {
+        &self.rows[..self.size.height]
+    }
+
+    /// Returns the bitmask of a row where every cell is filled.
+    #[inline]
+    pub(crate) fn full_row(&self) -> u16 {
+        self.full_row
+    }
+
+    /// Recomputes the column heights from the rows.
+    fn update_heights(&mut self) {
+        let mut seen = 0;
+        self.heights = [0; MAX_BOARD_WIDTH];
+        for (r, row) in self.rows[..self.size.height].iter().enumerate() {
+            let mut new = row & !seen;
+            while new != 0 {
+                let c = new.trailing_zeros() as usize;
+                self.heights[c] = self.size.height - r;
+                new &= new - 1;
+            }
+            seen |= row;
+            if seen == self.full_row {
+                break;
+            }
+        }
+    }
+
+    /// Shifts a pattern row to the given column, or returns `None` if it is outside of the board.
+    #[inline]
+    fn shift_row(&self, mask: u16, col: isize) -> Option<u16> {
+        let shifted = if col >= 0 {
+            (mask as u32) << col
+        } else if mask & ((1 << -col) - 1) != 0 {
+            return None; // cells left of the board
+        } else {
+            (mask >> -col) as u32
+        };
+        if shifted & !(self.full_row as u32) != 0 {
+            return None; // cells right of the board
+        }
+        Some(shifted as u16)
     }
 
     /// Imprints the given matrix onto the board at the given row and column.
     pub(crate) fn imprint(&mut self, pattern: Pattern, row: isize, col: isize, cell: Cell) {
-        for (r_offset, p_row) in pattern.iter_rows().enumerate() {
-            for (c_offset, filled) in p_row.iter().enumerate() {
-                let r = row + r_offset as isize;
-                let c = col + c_offset as isize;
-                if *filled
-                    && r >= 0
-                    && r < BOARD_HEIGHT as isize
-                    && c >= 0
-                    && c < BOARD_WIDTH as isize
-                {
-                    let (r, c) = (r as usize, c as usize);
-                    self.data[r][c] = cell;
-                    self.heights[c] = self.heights[c].max(BOARD_HEIGHT - r);
-                }
+        for (r_offset, mask) in pattern.iter_rows().enumerate() {
+            let r = row + r_offset as isize;
+            if mask == 0 || r < 0 || r >= self.size.height as isize {
+                continue;
             }
+            let r = r as usize;
+            let shifted = self
+                .shift_row(mask, col)
+                .expect("pattern outside of the board");
+            self.rows[r] |= shifted;
+            let mut bits = shifted;
+            while bits != 0 {
+                let c = bits.trailing_zeros() as usize;
+                self.data[r][c] = cell;
+                self.heights[c] = self.heights[c].max(self.size.height - r);
+                bits &= bits - 1;
+            }
+        }
+    }
+
+    /// Returns true if no cell of the board is filled.
+    pub fn is_empty(&self) -> bool {
+        self.rows().iter().all(|&row| row == 0)
+    }
+
+    /// Returns true if the cell is filled or outside of the board, except for the rows above it.
+    pub(crate) fn is_blocked(&self, row: isize, col: isize) -> bool {
+        if col < 0 || col >= self.size.width as isize || row >= self.size.height as isize {
+            return true;
         }
+        row >= 0 && self.rows[row as usize] & (1 << col) != 0
     }
 
     /// Returns true if the move overlaps with the board.
     pub(crate) fn overlaps_move(&self, r#move: Move) -> bool {
         let pattern = r#move.pattern();
-        for (r, p_row) in pattern.iter_rows().enumerate() {
-            for (c, filled) in p_row.iter().enumerate() {
-                if !*filled {
-                    continue;
-                }
-                let row = r as isize + r#move.pos.row;
-                let col = c as isize + r#move.pos.col;
-                if row >= BOARD_HEIGHT as isize || col < 0 || col >= BOARD_WIDTH as isize {
-                    return true; // collision with the bottom or sides
-                } else if row >= 0 // in bounds of board
-                    && row < BOARD_HEIGHT as isize
-                    && col >= 0
-                    && col < BOARD_WIDTH as isize
-                    && self.data[row as usize][col as usize].filled()
-                {
-                    return true; // collision with a filled cell
-                }
+        for (r, mask) in pattern.iter_rows().enumerate() {
+            if mask == 0 {
+                continue;
+            }
+            let row = r as isize + r#move.pos.row;
+            if row >= self.size.height as isize {
+                return true; // collision with the bottom
+            }
+            let Some(shifted) = self.shift_row(mask, r#move.pos.col) else {
+                return true; // collision with the sides
+            };
+            if row >= 0 && self.rows[row as usize] & shifted != 0 {
+                return true; // collision with a filled cell
             }
         }
         false
     }
 
     /// Clears the full rows and returns the indices of rows cleared.
-    pub(crate) fn clear_full(&mut self) -> Vec<usize> {
-        let mut rows = Vec::new();
-        let mut bottom = (BOARD_HEIGHT - 1) as i64;
-        let mut total_shift = 0;
-        while bottom >= 0 {
-            let mut top = bottom;
-            while top >= 0
-                && self.data[top as usize]
-                    .into_iter()
-                    .all(|cell| cell.filled())
-            {
-                rows.push(top as usize - total_shift);
-                top -= 1;
-            }
-            if top == bottom {
-                bottom -= 1;
+    pub(crate) fn clear_full(&mut self) -> ClearedRows {
+        let mut cleared = ClearedRows::default();
+        // move every row that is not full down to the next free row
+        let mut write = self.size.height;
+        for r in (0..self.size.height).rev() {
+            if self.rows[r] == self.full_row {
+                cleared.0 |= 1 << r;
                 continue;
             }
-            // shift rows down
-            let shift = (bottom - top) as usize;
-            total_shift += shift;
-            if shift < BOARD_HEIGHT {
-                for r in (0..(top + 1) as usize).rev() {
-                    for c in 0..BOARD_WIDTH {
-                        self.data[r + shift][c] = self.data[r][c]; // shift down
-                        self.data[r][c] = Cell::default(); // clear row
-                    }
-                }
-            }
-            // clear the rest of the rows
-            for r in (top + 1) as usize..shift {
-                for c in 0..BOARD_WIDTH {
-                    self.data[r][c] = Cell::default(); // clear row
-                }
+            write -= 1;
+            if write != r {
+                self.rows[write] = self.rows[r];
+                self.data[write] = self.data[r];
             }
         }
-        let rows_cleared = rows.len();
-        if rows_cleared > 0 {
-            // update heights accordingly
-            'outer: for c in 0..BOARD_WIDTH {
-                let max_height = self.heights[c] - rows_cleared;
-                for r in (BOARD_HEIGHT - max_height)..BOARD_HEIGHT {
-                    if self.data[r][c].filled() {
-                        self.heights[c] = BOARD_HEIGHT - r;
-                        continue 'outer;
-                    }
-                }
-                self.heights[c] = 0;
+        if !cleared.is_empty() {
+            for r in 0..write {
+                self.rows[r] = 0;
+                self.data[r] = [Cell::default(); MAX_BOARD_WIDTH];
             }
+            self.update_heights();
         }
-        rows
+        cleared
     }
 
     #[cfg(test)]
-    pub(crate) fn get_data(&self) -> &[[Cell; BOARD_WIDTH]; BOARD_HEIGHT] {
-        &self.data
+    pub(crate) fn get_data(&self) -> Vec<&[Cell]> {
+        self.data[..self.size.height]
+            .iter()
+            .map(|row| &row[..self.size.width])
+            .collect()
     }
 
+    /// Returns the cells of the rows, from top to bottom.
     #[cfg(feature = "wasm")]
-    pub(crate) fn get_raw_data(&self) -> &[[u8; BOARD_WIDTH]; BOARD_HEIGHT] {
-        // SAFETY: Cell is repr(transparent)
-        unsafe { std::mem::transmute(&self.data) }
+    pub(crate) fn get_raw_data(&self) -> Vec<&[u8]> {
+        self.data[..self.size.height]
+            .iter()
+            .map(|row| {
+                // SAFETY: Cell is repr(transparent)
+                let row: &[u8; MAX_BOARD_WIDTH] = unsafe { std::mem::transmute(row) };
+                &row[..self.size.width]
+            })
+            .collect()
     }
 
     #[cfg(test)]
     pub(crate) fn fill_cell(&mut self, row: usize, col: usize) {
+        self.rows[row] |= 1 << col;
         self.data[row][col] = Cell::new(1);
-        self.heights[col] = self.heights[col].max(BOARD_HEIGHT - row);
+        self.heights[col] = self.heights[col].max(self.size.height - row);
     }
 
     #[cfg(test)]
     pub(crate) fn clear_cell(&mut self, row: usize, col: usize) {
+        self.rows[row] &= !(1 << col);
         self.data[row][col] = Cell::default();
         // if the top cell was cleared
-        if self.heights[col] == BOARD_HEIGHT - row {
-            // find the highest cell in the column
-            for r in 0..BOARD_HEIGHT {
-                if self.data[r][col].filled() {
-                    self.heights[col] = BOARD_HEIGHT - r;
-                    return;
-                }
-            }
-            self.heights[col] = 0; // reset to 0 if column is empty
+        if self.heights[col] == self.size.height - row {
+            self.update_heights();
         }
     }
 }
@@ -221,9 +369,9 @@ impl Index<(usize, usize)> for Board {
 impl Display for Board {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f)?;
-        for r in 0..BOARD_HEIGHT {
-            for c in 0..BOARD_WIDTH {
-                write!(f, "{}", self.data[r][c])?;
+        for row in self.data[..self.size.height].iter() {
+            for cell in row[..self.size.width].iter() {
+                write!(f, "{}", cell)?;
             }
             writeln!(f)?;
         }
@@ -234,29 +382,43 @@ impl Display for Board {
 impl FromStr for Board {
     type Err = ();
 
+    /// Parses a board from rows of `#` and `.`, the size of the board is taken from the input.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let lines = s.trim().lines().collect::<Vec<_>>();
-        assert!(lines.len() == BOARD_HEIGHT);
-        let mut data = [[Cell::default(); BOARD_WIDTH]; BOARD_HEIGHT];
+        let lines = s.trim().lines().map(str::trim).collect::<Vec<_>>();
+        let width = lines.first().map_or(0, |line| line.len());
+        let size = BoardSize::new(width, lines.len(), 0).ok_or(())?;
+        let mut board = Board::new(size);
         for (r, line) in lines.iter().enumerate() {
-            assert!(line.len() == BOARD_WIDTH);
+            if line.len() != width {
+                return Err(());
+            }
             for (c, ch) in line.chars().enumerate() {
-                if ch == '#' {
-                    data[r][c] = Cell::new(1);
-                } else if ch == '.' {
-                    data[r][c] = Cell::default();
-                } else {
-                    panic!("invalid character: {}", ch);
+                match ch {
+                    '#' => {
+                        board.rows[r] |= 1 << c;
+                        board.data[r][c] = Cell::new(1);
+                    }
+                    '.' => {}
+                    _ => return Err(()),
                 }
             }
         }
-        Ok(Board::from_data(data))
+        board.update_heights();
+        Ok(board)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        r#move::Position,
+        piece::{N_PIECES, Piece},
+    };
+
+    fn cleared(board: &mut Board) -> Vec<usize> {
+        board.clear_full().iter().collect()
+    }
 
     fn fill_row(board: &mut Board, row: usize) {
         for c in 0..BOARD_WIDTH {
@@ -264,11 +426,92 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_overlaps_move_matches_cells() {
+        for _ in 0..100 {
+            let board = crate::test::random_board();
+            for index in 0..N_PIECES {
+                let piece = Piece::from_index(index);
+                for rot in 0..piece.num_rotations() {
+                    for row in -3..BOARD_HEIGHT as isize + 1 {
+                        for col in -3..BOARD_WIDTH as isize + 1 {
+                            let r#move = Move {
+                                piece,
+                                pos: Position { rot, row, col },
+                            };
+                            assert_eq!(
+                                board.overlaps_move(r#move),
+                                overlaps_cells(&board, r#move),
+                                "{move:?} on {board}"
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Checks for overlap cell by cell.
+    fn overlaps_cells(board: &Board, r#move: Move) -> bool {
+        let pattern = r#move.pattern();
+        for r in 0..pattern.rows() {
+            for c in 0..pattern.cols() {
+                if !pattern.filled(r, c) {
+                    continue;
+                }
+                let row = r as isize + r#move.pos.row;
+                let col = c as isize + r#move.pos.col;
+                if row >= BOARD_HEIGHT as isize || col < 0 || col >= BOARD_WIDTH as isize {
+                    return true;
+                }
+                if row >= 0 && board[(row as usize, col as usize)].filled() {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    #[test]
+    fn test_parse_board_size() {
+        assert_eq!("10x20".parse(), Ok(BoardSize::default()));
+        assert_eq!("10x20+20".parse(), BoardSize::new(10, 40, 20).ok_or(()));
+        assert_eq!("4x20".parse(), BoardSize::new(4, 20, 0).ok_or(()));
+        assert!("17x20".parse::<BoardSize>().is_err());
+        assert!("10x3".parse::<BoardSize>().is_err());
+        assert!("10x30+20".parse::<BoardSize>().is_err());
+        assert!("10".parse::<BoardSize>().is_err());
+        assert!(
+            format!("10x20+{}", usize::MAX)
+                .parse::<BoardSize>()
+                .is_err()
+        );
+        // more hidden rows than rows in total
+        assert_eq!(BoardSize::new(10, 5, 10), None);
+    }
+
+    #[test]
+    fn test_clear_board_small() {
+        let mut board = Board::from_str(
+            r#"
+....
+#.#.
+####
+.###"#,
+        )
+        .unwrap();
+        assert_eq!(board.size(), BoardSize::new(4, 4, 0).unwrap());
+        assert_eq!(board.heights(), &[3, 2, 3, 2]);
+        assert_eq!(cleared(&mut board), vec![2]);
+        assert_eq!(board.to_string(), "\n....\n....\n#.#.\n.###\n");
+        assert_eq!(board.heights(), &[2, 1, 2, 1]);
+    }
+
     #[test]
     fn test_clear_board_single() {
         let mut board = Board::default();
         fill_row(&mut board, BOARD_HEIGHT - 1);
-        assert_eq!(board.clear_full(), vec![BOARD_HEIGHT - 1]);
+        assert_eq!(cleared(&mut board), vec![BOARD_HEIGHT - 1]);
         assert_eq!(board.heights(), &[0; BOARD_WIDTH]);
     }
 
@@ -276,14 +519,14 @@ mod tests {
     fn test_clear_board_single_2() {
         let mut board = Board::default();
         fill_row(&mut board, 8);
-        assert_eq!(board.clear_full(), vec![8]);
+        assert_eq!(cleared(&mut board), vec![8]);
     }
 
     #[test]
     fn test_clear_board_single_3() {
         let mut board = Board::default();
         fill_row(&mut board, 0);
-        assert_eq!(board.clear_full(), vec![0]);
+        assert_eq!(cleared(&mut board), vec![0]);
     }
 
     #[test]
@@ -294,7 +537,7 @@ mod tests {
             for r in range.clone() {
                 fill_row(&mut board, r);
             }
-            assert_eq!(board.clear_full(), range.rev().collect::<Vec<_>>());
+            assert_eq!(cleared(&mut board), range.rev().collect::<Vec<_>>());
             if n >= 3 {
                 // last three are valid Tetris boards
                 assert_eq!(board.heights(), &[0; BOARD_WIDTH]);
@@ -310,7 +553,7 @@ mod tests {
             for r in range.clone() {
                 fill_row(&mut board, r);
             }
-            assert_eq!(board.clear_full(), range.rev().collect::<Vec<_>>());
+            assert_eq!(cleared(&mut board), range.rev().collect::<Vec<_>>());
             for r in 0..BOARD_HEIGHT {
                 for c in 0..BOARD_WIDTH {
                     assert!(board[(r, c)].empty());
@@ -322,12 +565,13 @@ mod tests {
 
     fn test_clear_board(input: &str, expected: &str, expected_rows: Vec<usize>) {
         let mut board = Board::from_str(input).unwrap();
-        let cleared = board.clear_full();
+        let cleared = cleared(&mut board);
         let expected_board = Board::from_str(expected).unwrap();
         println!("Expected board:{}", expected);
         println!("Actual board:{}", board);
         assert_eq!(cleared, expected_rows);
         assert_eq!(board.heights, expected_board.heights);
+        assert_eq!(board.rows, expected_board.rows);
         assert_eq!(board.data, expected_board.data);
     }
 
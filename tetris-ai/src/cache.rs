@@ -0,0 +1,211 @@
+use crate::{
+    board::{Board, MAX_BOARD_HEIGHT, MAX_BOARD_WIDTH},
+    state::State,
+};
+
+/// The values of each column and row of a board that features are built from.
+/// The cache of a state can be updated from the cache of its parent state, which only recomputes
+/// the rows around the placed piece instead of scanning the whole board.
+/// The column heights are not cached here, as the board already updates them when a piece is placed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BoardCache {
+    width: usize,
+    height: usize,
+    /// The number of pits in each column.
+    holes: [u8; MAX_BOARD_WIDTH],
+    /// The number of times that two adjacent cells in each column mismatch.
+    col_trans: [u8; MAX_BOARD_WIDTH],
+    /// The number of times that two adjacent cells in each row mismatch.
+    row_trans: [u8; MAX_BOARD_HEIGHT],
+}
+
+impl BoardCache {
+    /// Computes the cache of a board from scratch.
+    pub fn new(board: &Board) -> Self {
+        let rows = board.rows();
+        let mut cache = Self {
+            width: board.width(),
+            height: rows.len(),
+            holes: [0; MAX_BOARD_WIDTH],
+            col_trans: [0; MAX_BOARD_WIDTH],
+            row_trans: [0; MAX_BOARD_HEIGHT],
+        };
+        let mut filled = [0; MAX_BOARD_WIDTH];
+        for (r, &row) in rows.iter().enumerate() {
+            for_each_col(row, |c| filled[c] += 1);
+            cache.update_row(board, r);
+        }
+        for (c, holes) in cache.holes[..cache.width].iter_mut().enumerate() {
+            *holes = (board.height(c) - filled[c]) as u8;
+        }
+        cache.add_col_trans(rows, 0..rows.len());
+        cache
+    }
+
+    /// Returns the cache of the board of `state`, which is `parent` after placing the piece of its delta.
+    /// Only the transitions of the rows around the piece are recomputed, the rows above the piece
+    /// move down by the number of cleared rows, which are all rows of the piece.
+    pub fn update(&self, parent: &State, state: &State) -> Self {
+        let (old, new) = (parent.board(), state.board());
+        let r#move = state.delta().expect("state without a placed piece").r#move;
+        let cleared = (state.stats().lines - parent.stats().lines) as usize;
+        let pattern = r#move.pattern();
+        let bounds = pattern.bounds();
+        let top = (r#move.pos.row + bounds.top as isize).max(0) as usize;
+        let bottom = (r#move.pos.row + bounds.bottom as isize).max(0) as usize;
+        let mut cache = *self;
+
+        // the piece fills cells in its columns, and a cleared row empties a cell in every column
+        let mut piece = [0; MAX_BOARD_WIDTH];
+        for (offset, mask) in pattern.iter_rows().enumerate() {
+            if r#move.pos.row + offset as isize >= 0 {
+                for_each_col(mask, |c| piece[(r#move.pos.col + c as isize) as usize] += 1);
+            }
+        }
+        // without cleared rows only the columns of the piece change
+        let columns = if cleared > 0 {
+            0..cache.width
+        } else {
+            let left = (r#move.pos.col + bounds.left as isize) as usize;
+            left..left + bounds.cols()
+        };
+        for c in columns {
+            let filled = old.height(c) - self.holes[c] as usize + piece[c] - cleared;
+            cache.holes[c] = (new.height(c) - filled) as u8;
+        }
+
+        // the pairs of rows that touch the piece are replaced, the pairs above it move down
+        cache.remove_col_trans(old.rows(), top.saturating_sub(1)..bottom);
+        cache.add_col_trans(new.rows(), (top + cleared).saturating_sub(1)..bottom);
+        if cleared > 0 && top > 0 {
+            // the empty rows that came in at the top meet the old top row
+            cache.add_col_trans(new.rows(), cleared - 1..cleared);
+        }
+
+        cache.row_trans.copy_within(0..top, cleared);
+        cache.row_trans[..cleared].fill(0);
+        for r in top + cleared..bottom {
+            cache.update_row(new, r);
+        }
+        cache
+    }
+
+    /// Adds the column transitions between each row in the range and the row below it.
+    fn add_col_trans(&mut self, rows: &[u16], range: std::ops::Range<usize>) {
+        for r in range.start..range.end.min(rows.len() - 1) {
+            for_each_col(rows[r] ^ rows[r + 1], |c| self.col_trans[c] += 1);
+        }
+    }
+
+    fn remove_col_trans(&mut self, rows: &[u16], range: std::ops::Range<usize>) {
+        for r in range.start..range.end.min(rows.len() - 1) {
+            for_each_col(rows[r] ^ rows[r + 1], |c| self.col_trans[c] -= 1);
+        }
+    }
+
+    fn update_row(&mut self, board: &Board, r: usize) {
+        let row = board.rows()[r];
+        let inner = board.full_row() >> 1; // the cells that have a neighbor to the right
+        self.row_trans[r] = ((row ^ (row >> 1)) & inner).count_ones() as u8;
+    }
+
+    /// Returns the number of pits in each column.
+    pub fn holes(&self) -> &[u8] {
+        &self.holes[..self.width]
+    }
+
+    /// Returns the number of column transitions in each column.
+    pub fn col_trans(&self) -> &[u8] {
+        &self.col_trans[..self.width]
+    }
+
+    /// Returns the number of row transitions in each row, from top to bottom.
+    pub fn row_trans(&self) -> &[u8] {
+        &self.row_trans[..self.height]
+    }
+}
+
+/// Calls `f` with the index of every filled cell in the row.
+#[inline]
+fn for_each_col(mut row: u16, mut f: impl FnMut(usize)) {
+    while row != 0 {
+        f(row.trailing_zeros() as usize);
+        row &= row - 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        feature::{Feature, FeatureRegistry},
+        r#move::move_dijkstra,
+        piece::{N_PIECES, Piece},
+        state::State,
+        test,
+    };
+    use std::sync::Arc;
+
+    const TEST_ITERATIONS: usize = 50;
+    const CHAINED_ITERATIONS: usize = 4;
+
+    /// Checks the updated cache of every placement on the parent, and of the placements after them.
+    fn check_placements(
+        parent: &State,
+        parent_cache: &BoardCache,
+        features: &[Arc<dyn Feature>],
+        depth: usize,
+        cleared: &mut u64,
+    ) {
+        for piece in (0..N_PIECES).map(Piece::from_index) {
+            for path in move_dijkstra(parent.board(), piece, None) {
+                let r#move = path.final_move();
+                let state = parent.future(r#move, None);
+                let cache = parent_cache.update(parent, &state);
+                assert_eq!(
+                    cache,
+                    BoardCache::new(state.board()),
+                    "Mismatch after placing {:?}\nBoard {}",
+                    r#move,
+                    state.board()
+                );
+                *cleared += state.stats().lines - parent.stats().lines;
+                for feature in features.iter() {
+                    if let Some(value) = feature.compute_cached(&state, &cache) {
+                        assert_eq!(value, feature.compute(&state), "Mismatch for {:?}", feature);
+                    }
+                }
+                if depth > 1 {
+                    check_placements(&state, &cache, features, depth - 1, cleared);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_incremental_equals_full() {
+        let features = FeatureRegistry::names()
+            .iter()
+            .filter_map(|name| FeatureRegistry::get(name))
+            .collect::<Vec<_>>();
+        let mut cleared = 0;
+        for i in 0..TEST_ITERATIONS {
+            // the random states are mostly topped out, the random boards leave room to place pieces
+            let parent = if i % 2 == 0 {
+                test::random_state()
+            } else {
+                State::new(test::random_board())
+            };
+            // the caches are carried through the search tree, so the updates are chained
+            let depth = if i < CHAINED_ITERATIONS { 2 } else { 1 };
+            check_placements(
+                &parent,
+                &BoardCache::new(parent.board()),
+                &features,
+                depth,
+                &mut cleared,
+            );
+        }
+        assert!(cleared > 0);
+    }
+}
@@ -1,26 +1,243 @@
-use crate::{
-    board::{BOARD_HEIGHT, BOARD_WIDTH},
-    state::State,
-};
+use crate::{board::MAX_BOARD_WIDTH, cache::BoardCache, r#move::Placement, state::State};
 use serde::Serialize;
 use std::cmp::{max, min};
+use std::sync::{Arc, LazyLock, RwLock};
 #[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::{JsValue, wasm_bindgen};
 
 type FeatureFn = fn(&State) -> f64;
+type CachedFeatureFn = fn(&BoardCache) -> f64;
 
-/// This defines all the available features by mapping their names to their respective functions.
+/// A feature rates a state with a single value, which is weighted by the policy.
+/// Features are selected by their name, see [`FeatureRegistry`].
+pub trait Feature: Send + Sync {
+    /// The unique name of the feature, as used in weights and presets.
+    fn name(&self) -> &str;
+    /// A short description of what the feature measures.
+    fn description(&self) -> &str;
+    fn compute(&self, state: &State) -> f64;
+
+    /// Computes the feature from the cached values of the board of the state, see [`BoardCache`].
+    /// Returns `None` if the feature does not use the cache, then [`Feature::compute`] is used instead.
+    fn compute_cached(&self, _state: &State, _cache: &BoardCache) -> Option<f64> {
+        None
+    }
+}
+
+impl std::fmt::Debug for dyn Feature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A feature implemented by a function.
+struct FnFeature {
+    name: &'static str,
+    description: &'static str,
+    compute: FeatureFn,
+    cached: Option<CachedFeatureFn>,
+}
+
+impl Feature for FnFeature {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn description(&self) -> &str {
+        self.description
+    }
+
+    fn compute(&self, state: &State) -> f64 {
+        (self.compute)(state)
+    }
+
+    fn compute_cached(&self, _state: &State, cache: &BoardCache) -> Option<f64> {
+        self.cached.map(|cached| cached(cache))
+    }
+}
+
+/// This defines all the built-in features by mapping their names to their respective functions and descriptions.
 /// To add a new feature, you can simply add a new entry to this array.
-const FEATURE_LOOKUP: &[(&str, FeatureFn)] = &[
-    ("col_trans", col_trans),
-    ("row_trans", row_trans),
-    ("pits", pits),
-    ("landing_height", landing_height),
-    ("eroded_cells", eroded_cells),
-    ("cuml_wells", cuml_wells),
+const FEATURE_LOOKUP: &[(&str, FeatureFn, &str)] = &[
+    (
+        "col_trans",
+        col_trans,
+        "Adjacent cells in a column that mismatch",
+    ),
+    (
+        "row_trans",
+        row_trans,
+        "Adjacent cells in a row that mismatch",
+    ),
+    ("pits", pits, "Empty cells with a filled cell above them"),
+    (
+        "landing_height",
+        landing_height,
+        "Height of the middle of the placed piece",
+    ),
+    (
+        "padded_landing_height",
+        padded_landing_height,
+        "Height below the pattern of the placed piece",
+    ),
+    (
+        "eroded_cells",
+        eroded_cells,
+        "Cells of the placed piece that were cleared",
+    ),
+    (
+        "cuml_wells",
+        cuml_wells,
+        "Sum from 1 to the depth of each well",
+    ),
+    (
+        "tucks",
+        tucks,
+        "Whether the placed piece was tucked under an overhang",
+    ),
+    (
+        "spins",
+        spins,
+        "Whether the placed piece was spun under an overhang",
+    ),
+    ("agg_height", agg_height, "Sum of the column heights"),
+    ("max_height", max_height, "Height of the highest column"),
+    (
+        "bumpiness",
+        bumpiness,
+        "Sum of the height differences between adjacent columns",
+    ),
+    ("row_holes", row_holes, "Rows that contain at least one pit"),
+    (
+        "hole_depth",
+        hole_depth,
+        "Sum of the cells above each pit up to the top of its column",
+    ),
+    ("well_depth", well_depth, "Sum of the depths of the wells"),
+    ("deepest_well", deepest_well, "Depth of the deepest well"),
+    (
+        "true_landing_height",
+        true_landing_height,
+        "Height of the bottom of the placed piece",
+    ),
+    (
+        "bcts_hole_depth",
+        bcts_hole_depth,
+        "Sum of the filled cells above each pit",
+    ),
     // Add new features here
 ];
 
+/// The built-in features that can also be computed from a [`BoardCache`], which must give the same values.
+const CACHED_LOOKUP: &[(&str, CachedFeatureFn)] = &[
+    ("col_trans", |cache| sum(cache.col_trans())),
+    ("row_trans", |cache| sum(cache.row_trans())),
+    ("pits", |cache| sum(cache.holes())),
+];
+
+fn sum(values: &[u8]) -> f64 {
+    values.iter().map(|&value| value as u32).sum::<u32>() as f64
+}
+
+/// Creates a feature from the parameter in its name, e.g. `5` for `holes_above:5`.
+/// Returns `None` if the parameter is invalid.
+pub type FeatureFamily = Box<dyn Fn(&str) -> Option<Arc<dyn Feature>> + Send + Sync>;
+
+/// The features that can be selected by name, which are the built-in features
+/// and the features that were registered at runtime.
+/// Parameterised features are named `<prefix>:<parameter>`, e.g. `holes_above:5` or `height_above:12`.
+pub struct FeatureRegistry {
+    features: Vec<Arc<dyn Feature>>,
+    families: Vec<(String, FeatureFamily)>,
+}
+
+static REGISTRY: LazyLock<RwLock<FeatureRegistry>> =
+    LazyLock::new(|| RwLock::new(FeatureRegistry::builtin()));
+
+impl FeatureRegistry {
+    fn builtin() -> Self {
+        let features = FEATURE_LOOKUP
+            .iter()
+            .map(|&(name, compute, description)| {
+                let cached = CACHED_LOOKUP
+                    .iter()
+                    .find(|(n, _)| *n == name)
+                    .map(|&(_, cached)| cached);
+                Arc::new(FnFeature {
+                    name,
+                    description,
+                    compute,
+                    cached,
+                }) as Arc<dyn Feature>
+            })
+            .collect();
+        let families: Vec<(String, FeatureFamily)> = vec![
+            (
+                "holes_above".to_string(),
+                Box::new(|param| {
+                    let row = param.parse().ok()?;
+                    Some(Arc::new(HolesAbove::new(row)) as Arc<dyn Feature>)
+                }),
+            ),
+            (
+                "height_above".to_string(),
+                Box::new(|param| {
+                    let threshold = param.parse().ok()?;
+                    Some(Arc::new(HeightAbove::new(threshold)) as Arc<dyn Feature>)
+                }),
+            ),
+        ];
+        Self { features, families }
+    }
+
+    /// Registers a feature, which replaces a feature with the same name.
+    pub fn register(feature: impl Feature + 'static) {
+        REGISTRY.write().unwrap().add(Arc::new(feature));
+    }
+
+    /// Registers a family of parameterised features under a prefix, which replaces a family with the same prefix.
+    pub fn register_family(prefix: &str, family: FeatureFamily) {
+        REGISTRY.write().unwrap().add_family(prefix, family);
+    }
+
+    /// Finds a feature by its name, or creates it if it is parameterised.
+    pub fn get(name: &str) -> Option<Arc<dyn Feature>> {
+        REGISTRY.read().unwrap().find(name)
+    }
+
+    /// Returns the names of the registered features, without the parameterised features.
+    pub fn names() -> Vec<String> {
+        REGISTRY.read().unwrap().feature_names()
+    }
+
+    fn add(&mut self, feature: Arc<dyn Feature>) {
+        self.features.retain(|f| f.name() != feature.name());
+        self.features.push(feature);
+    }
+
+    fn add_family(&mut self, prefix: &str, family: FeatureFamily) {
+        self.families.retain(|(p, _)| p != prefix);
+        self.families.push((prefix.to_string(), family));
+    }
+
+    fn find(&self, name: &str) -> Option<Arc<dyn Feature>> {
+        if let Some(feature) = self.features.iter().find(|f| f.name() == name) {
+            return Some(feature.clone());
+        }
+        let (prefix, param) = name.split_once(':')?;
+        let (_, family) = self.families.iter().find(|(p, _)| p == prefix)?;
+        family(param)
+    }
+
+    fn feature_names(&self) -> Vec<String> {
+        self.features.iter().map(|f| f.name().to_string()).collect()
+    }
+
+    fn expect(name: &str) -> Arc<dyn Feature> {
+        Self::get(name).unwrap_or_else(|| panic!("Unknown feature: '{}'", name))
+    }
+}
+
 /// Presets can be used to quickly initialize a set of weights.
 const PRESETS: &[(&str, &[(&str, f64)])] = &[
     (
@@ -29,7 +246,7 @@ const PRESETS: &[(&str, &[(&str, f64)])] = &[
             ("col_trans", -6.8),
             ("row_trans", -2.7),
             ("pits", -12.7),
-            ("landing_height", -3.8),
+            ("padded_landing_height", -3.8),
             ("eroded_cells", -10.0),
             ("cuml_wells", -0.4),
         ],
@@ -40,7 +257,7 @@ const PRESETS: &[(&str, &[(&str, f64)])] = &[
             ("col_trans", -8.4),
             ("row_trans", -2.4),
             ("pits", -10.0),
-            ("landing_height", -5.0),
+            ("padded_landing_height", -5.0),
             ("eroded_cells", 10.0),
             ("cuml_wells", -3.5),
         ],
@@ -55,9 +272,9 @@ pub struct WeightsMap(Vec<(String, f64)>);
 impl Default for WeightsMap {
     fn default() -> Self {
         WeightsMap(
-            FEATURE_LOOKUP
-                .iter()
-                .map(|(name, _)| (name.to_string(), 0.0))
+            FeatureRegistry::names()
+                .into_iter()
+                .map(|name| (name, 0.0))
                 .collect::<Vec<_>>(),
         )
     }
@@ -128,20 +345,24 @@ impl From<Weights> for WeightsMap {
             weights
                 .0
                 .iter()
-                .map(|(feature_fn, weight)| {
-                    let name = FEATURE_LOOKUP
-                        .iter()
-                        .find(|(_, fn_ptr)| std::ptr::fn_addr_eq(*fn_ptr, *feature_fn))
-                        .expect("Feature function not found");
-                    (name.0.to_string(), *weight)
-                })
+                .map(|(feature, weight)| (feature.name().to_string(), *weight))
                 .collect::<Vec<_>>(),
         )
     }
 }
 
+/// The value of a feature for a state and how much it contributed to its evaluation.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FeatureContribution {
+    pub name: String,
+    pub value: f64,
+    pub weight: f64,
+    /// The value multiplied by the weight.
+    pub contribution: f64,
+}
+
 #[derive(Debug, Clone)]
-pub struct Weights(Vec<(FeatureFn, f64)>);
+pub struct Weights(Vec<(Arc<dyn Feature>, f64)>);
 
 impl Default for Weights {
     fn default() -> Self {
@@ -150,19 +371,16 @@ impl Default for Weights {
 }
 
 #[derive(Debug, Clone)]
-pub struct Features(Vec<FeatureFn>);
+pub struct Features(Vec<Arc<dyn Feature>>);
 
 impl Features {
     pub fn from_names(names: &[&str]) -> Self {
-        let mut features = Vec::with_capacity(names.len());
-        for name in names.iter() {
-            if let Some((_, feature)) = FEATURE_LOOKUP.iter().find(|(n, _)| n == name) {
-                features.push(*feature);
-            } else {
-                panic!("Unknown feature: {}", name);
-            }
-        }
-        Features(features)
+        Features(
+            names
+                .iter()
+                .map(|name| FeatureRegistry::expect(name))
+                .collect(),
+        )
     }
 
     pub fn len(&self) -> usize {
@@ -180,7 +398,7 @@ impl Features {
         Weights(
             self.0
                 .iter()
-                .copied()
+                .cloned()
                 .zip(weights.iter().copied())
                 .collect::<Vec<_>>(),
         )
@@ -197,21 +415,43 @@ impl Weights {
     }
 
     fn from_iter<'a>(iter: impl Iterator<Item = (&'a str, f64)>) -> Self {
-        let mut weights = Vec::with_capacity(FEATURE_LOOKUP.len());
-        for (name, weight) in iter.into_iter() {
-            if let Some((_, feature)) = FEATURE_LOOKUP.iter().find(|(n, _)| n == &name) {
-                weights.push((*feature, weight));
-            } else {
-                panic!("Unknown feature: '{}'", name);
-            }
-        }
-        Weights(weights)
+        Weights(
+            iter.map(|(name, weight)| (FeatureRegistry::expect(name), weight))
+                .collect(),
+        )
     }
 
     pub fn evaluate(&self, state: &State) -> f64 {
+        self.0.iter().fold(0.0, |acc, (feature, weight)| {
+            acc + feature.compute(state) * weight
+        })
+    }
+
+    /// Evaluates a state like [`Weights::evaluate`], using the cache of its board for the features
+    /// that support it, see [`Feature::compute_cached`].
+    pub fn evaluate_cached(&self, state: &State, cache: &BoardCache) -> f64 {
+        self.0.iter().fold(0.0, |acc, (feature, weight)| {
+            let value = feature
+                .compute_cached(state, cache)
+                .unwrap_or_else(|| feature.compute(state));
+            acc + value * weight
+        })
+    }
+
+    /// Evaluates a state like [`Weights::evaluate`], but returns the contribution of each feature.
+    pub fn breakdown(&self, state: &State) -> Vec<FeatureContribution> {
         self.0
             .iter()
-            .fold(0.0, |acc, (feature, weight)| acc + feature(state) * weight)
+            .map(|(feature, weight)| {
+                let value = feature.compute(state);
+                FeatureContribution {
+                    name: feature.name().to_string(),
+                    value,
+                    weight: *weight,
+                    contribution: value * weight,
+                }
+            })
+            .collect()
     }
 
     pub fn iter_values(&self) -> impl Iterator<Item = f64> {
@@ -229,41 +469,44 @@ impl Weights {
 
 /// The number of times that two adjacent cells in the same row mismatch.
 fn row_trans(state: &State) -> f64 {
-    let mut sum = 0;
-    for r in 0..BOARD_HEIGHT {
-        for c in 0..BOARD_WIDTH - 1 {
-            if state.board()[(r, c)].filled() != state.board()[(r, c + 1)].filled() {
-                sum += 1;
-            }
-        }
-    }
-    sum as f64
+    let board = state.board();
+    let inner = board.full_row() >> 1; // the cells that have a neighbor to the right
+    board
+        .rows()
+        .iter()
+        .map(|row| ((row ^ (row >> 1)) & inner).count_ones())
+        .sum::<u32>() as f64
 }
 
 /// The number of times that two adjacent cells in the same column mismatch.
 fn col_trans(state: &State) -> f64 {
-    let mut sum = 0;
-    for c in 0..BOARD_WIDTH {
-        for r in 0..BOARD_HEIGHT - 1 {
-            if state.board()[(r, c)].filled() != state.board()[(r + 1, c)].filled() {
-                sum += 1;
-            }
-        }
-    }
-    sum as f64
+    state
+        .board()
+        .rows()
+        .windows(2)
+        .map(|rows| (rows[0] ^ rows[1]).count_ones())
+        .sum::<u32>() as f64
 }
 
 /// The depth of each column with respect to its adjacent columns.
 /// If a column is not shorter than both of its neighbors, it has a value of 0.
 /// Otherwise, its value is how much shorter it is than its shortest neighbor.
-fn wells(state: &State) -> [i64; BOARD_WIDTH] {
-    let heights = state.board().heights().map(|x| x as i64);
-    let mut wells = [0i64; BOARD_WIDTH];
-    for i in 1..BOARD_WIDTH - 1 {
-        wells[i] = max(0, min(heights[i - 1], heights[i + 1]) - heights[i]);
-    }
-    wells[0] = max(0, heights[1] - heights[0]);
-    wells[BOARD_WIDTH - 1] = max(0, heights[BOARD_WIDTH - 2] - heights[BOARD_WIDTH - 1]);
+/// Columns outside of the board have a value of 0.
+fn wells(state: &State) -> [i64; MAX_BOARD_WIDTH] {
+    let heights = state.board().heights();
+    let width = heights.len();
+    let height = |c: usize| heights[c] as i64;
+    let mut wells = [0i64; MAX_BOARD_WIDTH];
+    for (i, well) in wells[..width].iter_mut().enumerate() {
+        // the walls are higher than any column
+        let left = i.checked_sub(1).map_or(i64::MAX, height);
+        let right = if i + 1 < width {
+            height(i + 1)
+        } else {
+            i64::MAX
+        };
+        *well = max(0, min(left, right) - height(i));
+    }
     wells
 }
 
@@ -279,19 +522,121 @@ fn cuml_wells(state: &State) -> f64 {
 /// The number of empty cells that have at least one filled cell above them.
 fn pits(state: &State) -> f64 {
     let mut total = 0;
-    for c in 0..BOARD_WIDTH {
-        for r in BOARD_HEIGHT - state.board().height(c)..BOARD_HEIGHT {
-            if state.board()[(r, c)].empty() {
-                total += 1;
-            }
+    let mut covered = 0; // the columns that have a filled cell above the current row
+    for row in state.board().rows() {
+        total += (covered & !row).count_ones();
+        covered |= row;
+    }
+    total as f64
+}
+
+/// The sum of the depths of all wells, see [`wells`].
+fn well_depth(state: &State) -> f64 {
+    wells(state).into_iter().sum::<i64>() as f64
+}
+
+/// The depth of the deepest well, see [`wells`].
+fn deepest_well(state: &State) -> f64 {
+    wells(state).into_iter().max().unwrap_or(0) as f64
+}
+
+/// The sum of the heights of all columns.
+fn agg_height(state: &State) -> f64 {
+    state.board().heights().iter().sum::<usize>() as f64
+}
+
+/// The height of the highest column.
+fn max_height(state: &State) -> f64 {
+    state.board().heights().iter().copied().max().unwrap_or(0) as f64
+}
+
+/// The sum of the height differences between adjacent columns.
+fn bumpiness(state: &State) -> f64 {
+    state
+        .board()
+        .heights()
+        .windows(2)
+        .map(|heights| heights[0].abs_diff(heights[1]))
+        .sum::<usize>() as f64
+}
+
+/// The number of rows that contain at least one pit, which is called rows with holes in BCTS.
+fn row_holes(state: &State) -> f64 {
+    let mut total = 0;
+    let mut covered = 0; // the columns that have a filled cell above the current row
+    for row in state.board().rows() {
+        if covered & !row != 0 {
+            total += 1;
+        }
+        covered |= row;
+    }
+    total as f64
+}
+
+/// The sum of the depths of all pits, where the depth is the number of cells above the pit
+/// up to the top of its column.
+fn hole_depth(state: &State) -> f64 {
+    let board = state.board();
+    let board_height = board.rows().len();
+    let heights = board.heights();
+    let mut total = 0;
+    let mut covered = 0;
+    for (r, row) in board.rows().iter().enumerate() {
+        let mut pits = covered & !row;
+        while pits != 0 {
+            let c = pits.trailing_zeros() as usize;
+            total += r - (board_height - heights[c]);
+            pits &= pits - 1;
+        }
+        covered |= row;
+    }
+    total as f64
+}
+
+/// The hole depth of BCTS: the number of filled cells above each pit, summed over all pits.
+fn bcts_hole_depth(state: &State) -> f64 {
+    let mut filled_above = [0; MAX_BOARD_WIDTH];
+    let mut total = 0;
+    let mut covered = 0;
+    for row in state.board().rows() {
+        let mut pits = covered & !row;
+        while pits != 0 {
+            total += filled_above[pits.trailing_zeros() as usize];
+            pits &= pits - 1;
+        }
+        let mut filled = *row;
+        while filled != 0 {
+            filled_above[filled.trailing_zeros() as usize] += 1;
+            filled &= filled - 1;
         }
+        covered |= row;
     }
     total as f64
 }
 
-/// The height of the row containing the bottom-most cell of the previously placed piece.
+/// The height of the row containing the bottom-most filled cell of the previously placed piece.
+fn true_landing_height(state: &State) -> f64 {
+    let board_height = state.board().rows().len();
+    let bottom = state.delta().map_or(0, |delta| {
+        let bounds = delta.r#move.pattern().bounds();
+        (delta.r#move.pos.row + bounds.bottom as isize).max(0) as usize
+    });
+    (board_height - bottom.min(board_height)) as f64
+}
+
+/// The height of the middle of the previously placed piece, as defined by Dellacherie.
 fn landing_height(state: &State) -> f64 {
-    (BOARD_HEIGHT
+    let piece_rows = state
+        .delta()
+        .map_or(0, |delta| delta.r#move.pattern().bounds().rows());
+    true_landing_height(state) + piece_rows as f64 / 2.0
+}
+
+/// The height of the row below the pattern of the previously placed piece, including its empty rows.
+/// This was the landing height before it used the bounds of the filled cells, the presets were tuned with it.
+fn padded_landing_height(state: &State) -> f64 {
+    let board_height = state.board().rows().len();
+    (board_height
         - state
             .delta()
             .as_ref()
@@ -300,7 +645,7 @@ fn landing_height(state: &State) -> f64 {
                 delta.r#move.pos.row.max(0) as usize + piece_height
             })
             .unwrap_or(0)
-            .min(BOARD_HEIGHT)) as f64
+            .min(board_height)) as f64
 }
 
 /// The number of cells that were cleared from the previously placed piece.
@@ -308,10 +653,102 @@ fn eroded_cells(state: &State) -> f64 {
     state.delta().map(|delta| delta.eroded).unwrap_or(0) as f64
 }
 
+/// The number of pits that are higher than the given number of rows from the bottom of the board.
+struct HolesAbove {
+    row: usize,
+    name: String,
+    description: String,
+}
+
+impl HolesAbove {
+    fn new(row: usize) -> Self {
+        Self {
+            row,
+            name: format!("holes_above:{row}"),
+            description: format!("Pits more than {row} rows above the bottom"),
+        }
+    }
+}
+
+impl Feature for HolesAbove {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn compute(&self, state: &State) -> f64 {
+        let rows = state.board().rows();
+        let mut total = 0;
+        let mut covered = 0;
+        for row in &rows[..rows.len().saturating_sub(self.row)] {
+            total += (covered & !row).count_ones();
+            covered |= row;
+        }
+        total as f64
+    }
+}
+
+/// The number of cells that the columns are higher than the given threshold, summed over all columns.
+struct HeightAbove {
+    threshold: usize,
+    name: String,
+    description: String,
+}
+
+impl HeightAbove {
+    fn new(threshold: usize) -> Self {
+        Self {
+            threshold,
+            name: format!("height_above:{threshold}"),
+            description: format!("Sum of the column heights above {threshold}"),
+        }
+    }
+}
+
+impl Feature for HeightAbove {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn compute(&self, state: &State) -> f64 {
+        state
+            .board()
+            .heights()
+            .iter()
+            .map(|height| height.saturating_sub(self.threshold))
+            .sum::<usize>() as f64
+    }
+}
+
+/// Whether the previously placed piece was tucked under an overhang, see [`Placement::Tuck`].
+fn tucks(state: &State) -> f64 {
+    placed(state, Placement::Tuck)
+}
+
+/// Whether the previously placed piece was spun under an overhang, see [`Placement::Spin`].
+fn spins(state: &State) -> f64 {
+    placed(state, Placement::Spin)
+}
+
+fn placed(state: &State, placement: Placement) -> f64 {
+    match state.delta() {
+        Some(delta) if delta.placement == placement => 1.0,
+        _ => 0.0,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{
+        board::BoardSize,
         r#move::{Move, Position},
         piece::Piece,
         state::Delta,
@@ -324,17 +761,35 @@ mod tests {
         ("col_trans", col_trans),
         ("row_trans", row_trans),
         ("pits", pits),
-        // ("landing_height", landing_height), // NOTE: differs from Python since piece includes empty rows/columns
+        ("landing_height", true_landing_height), // Python measures the bottom of the piece
         ("eroded_cells", eroded_cells),
         ("cuml_wells", cuml_wells),
     ];
 
+    /// Checks the middle of the piece against the bottom of the piece measured by Python.
+    fn check_landing_height(state: &State, feature_name: &str, py_output: usize) {
+        if feature_name != "landing_height" {
+            return;
+        }
+        let piece_rows = state
+            .delta()
+            .map_or(0, |delta| delta.r#move.pattern().bounds().rows());
+        assert_eq!(
+            landing_height(state),
+            py_output as f64 + piece_rows as f64 / 2.0,
+            "Mismatch for the middle landing height\nBoard {}\nDelta: {:?}",
+            state.board(),
+            state.delta()
+        );
+    }
+
     #[test]
     fn test_features_random_board() {
         for (feature_name, feature) in TEST_FEATURES {
             for _ in 0..TEST_ITERATIONS {
                 let state = State::new(test::random_board());
                 let py_output = test::run_py_feature(&state, feature_name);
+                check_landing_height(&state, feature_name, py_output);
                 let rust_output = feature(&state).round() as usize;
                 if py_output != rust_output {
                     panic!(
@@ -356,6 +811,7 @@ mod tests {
             for _ in 0..TEST_ITERATIONS {
                 let state = test::random_state();
                 let py_output = test::run_py_feature(&state, feature_name);
+                check_landing_height(&state, feature_name, py_output);
                 let rust_output = feature(&state).round() as usize;
                 if py_output != rust_output {
                     if let Some(delta) = state.delta() {
@@ -378,9 +834,223 @@ mod tests {
         }
     }
 
+    /// Reference implementations that scan the board cell by cell.
+    const CELL_FEATURES: &[(&str, FeatureFn)] = &[
+        ("row_trans", |state| {
+            let board = state.board();
+            let size = board.size();
+            let mut sum = 0;
+            for r in 0..size.height {
+                for c in 0..size.width - 1 {
+                    if board[(r, c)].filled() != board[(r, c + 1)].filled() {
+                        sum += 1;
+                    }
+                }
+            }
+            sum as f64
+        }),
+        ("col_trans", |state| {
+            let board = state.board();
+            let size = board.size();
+            let mut sum = 0;
+            for c in 0..size.width {
+                for r in 0..size.height - 1 {
+                    if board[(r, c)].filled() != board[(r + 1, c)].filled() {
+                        sum += 1;
+                    }
+                }
+            }
+            sum as f64
+        }),
+        ("cuml_wells", |state| {
+            let board = state.board();
+            let width = board.width() as isize;
+            let height = |c: isize| {
+                if c < 0 || c >= width {
+                    i64::MAX // the walls are higher than any column
+                } else {
+                    board.height(c as usize) as i64
+                }
+            };
+            (0..width)
+                .map(|c| (height(c - 1).min(height(c + 1)) - height(c)).max(0))
+                .map(|x| x * (x + 1) / 2)
+                .sum::<i64>() as f64
+        }),
+        ("pits", |state| {
+            let board = state.board();
+            let size = board.size();
+            let mut total = 0;
+            for c in 0..size.width {
+                for r in size.height - board.height(c)..size.height {
+                    if board[(r, c)].empty() {
+                        total += 1;
+                    }
+                }
+            }
+            total as f64
+        }),
+        ("row_holes", |state| {
+            let board = state.board();
+            let size = board.size();
+            (0..size.height)
+                .filter(|&r| {
+                    (0..size.width)
+                        .any(|c| board[(r, c)].empty() && r >= size.height - board.height(c))
+                })
+                .count() as f64
+        }),
+        ("hole_depth", |state| {
+            let board = state.board();
+            let size = board.size();
+            let mut total = 0;
+            for c in 0..size.width {
+                let top = size.height - board.height(c);
+                for r in top..size.height {
+                    if board[(r, c)].empty() {
+                        total += r - top;
+                    }
+                }
+            }
+            total as f64
+        }),
+        ("bcts_hole_depth", |state| {
+            let board = state.board();
+            let size = board.size();
+            let mut total = 0;
+            for c in 0..size.width {
+                let mut filled_above = 0;
+                for r in size.height - board.height(c)..size.height {
+                    if board[(r, c)].filled() {
+                        filled_above += 1;
+                    } else {
+                        total += filled_above;
+                    }
+                }
+            }
+            total as f64
+        }),
+        ("holes_above:5", |state| {
+            let board = state.board();
+            let size = board.size();
+            let mut total = 0;
+            for c in 0..size.width {
+                for r in size.height - board.height(c)..size.height.saturating_sub(5) {
+                    if board[(r, c)].empty() {
+                        total += 1;
+                    }
+                }
+            }
+            total as f64
+        }),
+        ("height_above:10", |state| {
+            let board = state.board();
+            (0..board.width())
+                .map(|c| board.height(c).saturating_sub(10))
+                .sum::<usize>() as f64
+        }),
+        ("bumpiness", |state| {
+            let board = state.board();
+            (1..board.width())
+                .map(|c| (board.height(c) as i64 - board.height(c - 1) as i64).abs())
+                .sum::<i64>() as f64
+        }),
+    ];
+
+    /// Standard, guideline with hidden rows, 4-wide, small and the widest boards.
+    const TEST_SIZES: &[BoardSize] = &[
+        BoardSize {
+            width: 10,
+            height: 20,
+            hidden: 0,
+        },
+        BoardSize {
+            width: 10,
+            height: 40,
+            hidden: 20,
+        },
+        BoardSize {
+            width: 4,
+            height: 20,
+            hidden: 0,
+        },
+        BoardSize {
+            width: 6,
+            height: 8,
+            hidden: 0,
+        },
+        BoardSize {
+            width: 16,
+            height: 24,
+            hidden: 0,
+        },
+    ];
+
+    #[test]
+    fn test_bitboard_features() {
+        for (feature_name, cell_feature) in CELL_FEATURES {
+            let feature = FeatureRegistry::get(feature_name).unwrap();
+            for i in 0..TEST_ITERATIONS {
+                let state = test::random_state_with_size(TEST_SIZES[i % TEST_SIZES.len()]);
+                assert_eq!(
+                    feature.compute(&state),
+                    cell_feature(&state),
+                    "Mismatch for feature {}\nBoard {}",
+                    feature_name,
+                    state.board()
+                );
+            }
+        }
+    }
+
+    struct Constant;
+
+    impl Feature for Constant {
+        fn name(&self) -> &str {
+            "test_constant"
+        }
+
+        fn description(&self) -> &str {
+            "Always 1"
+        }
+
+        fn compute(&self, _: &State) -> f64 {
+            1.0
+        }
+    }
+
+    #[test]
+    fn test_feature_registry() {
+        // a local registry, registering into the global one would leak into the defaults of other tests
+        let mut registry = FeatureRegistry::builtin();
+        registry.add(Arc::new(Constant));
+        assert!(
+            registry
+                .feature_names()
+                .contains(&"test_constant".to_string())
+        );
+        assert!(!FeatureRegistry::names().contains(&"test_constant".to_string()));
+        let weights = Weights(vec![
+            (registry.find("test_constant").unwrap(), 2.0),
+            (registry.find("holes_above:3").unwrap(), -1.0),
+        ]);
+        assert_eq!(weights.evaluate(&State::default()), 2.0);
+        let map = WeightsMap::from(weights);
+        assert_eq!(map.names(), ["test_constant", "holes_above:3"]);
+        assert!(registry.find("holes_above:x").is_none());
+        assert!(registry.find("unknown:3").is_none());
+
+        registry.add_family(
+            "test_constant",
+            Box::new(|_| Some(Arc::new(Constant) as Arc<dyn Feature>)),
+        );
+        assert!(registry.find("test_constant:1").is_some());
+        assert!(FeatureRegistry::get("test_constant:1").is_none());
+    }
+
     #[test]
     fn test_landing_heigt() {
-        let lh = landing_height(&State::default().test_delta(Delta {
+        let state = State::default().test_delta(Delta {
             r#move: Move {
                 piece: Piece::from_index(0),
                 pos: Position {
@@ -390,9 +1060,39 @@ mod tests {
                 },
             },
             eroded: 0,
-            cleared: vec![],
-        }));
+            tspin: None,
+            perfect_clear: false,
+            placement: Placement::HardDrop,
+            cleared: Default::default(),
+        });
         // 3 rows from the bottom of the board
-        assert_eq!(lh as usize, 3);
+        assert_eq!(padded_landing_height(&state) as usize, 3);
+        assert_eq!(true_landing_height(&state) as usize, 3);
+        // the middle of the vertical I is 2 rows higher
+        assert_eq!(landing_height(&state), 5.0);
+    }
+
+    #[test]
+    fn test_true_landing_height() {
+        let delta = Delta {
+            r#move: Move {
+                piece: Piece::from_index(0),
+                pos: Position {
+                    row: 16,
+                    col: 3,
+                    rot: 0,
+                },
+            },
+            eroded: 0,
+            tspin: None,
+            perfect_clear: false,
+            placement: Placement::HardDrop,
+            cleared: Default::default(),
+        };
+        let state = State::default().test_delta(delta);
+        // the horizontal I fills the third row of its pattern, 1 row above the bottom of the board
+        assert_eq!(true_landing_height(&state), 1.0);
+        assert_eq!(landing_height(&state), 1.5);
+        assert_eq!(padded_landing_height(&state), 0.0);
     }
 }
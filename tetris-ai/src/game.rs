@@ -1,57 +1,288 @@
-use crate::r#move::Move;
-use crate::rng::gen_random_piece;
+use crate::board::{Board, BoardSize};
+use crate::r#move::{Move, Position};
+use crate::piece::{MAX_ROTATIONS, Piece};
+use crate::queue::MAX_PREVIEW;
+use crate::rng::{GameRng, Randomizer, RandomizerKind, seeded_rng};
+use crate::rotation::{Nes, RotationSystem, RotationSystemKind};
+use crate::scoring::ScoringKind;
 use crate::state::State;
+use serde::Serialize;
 #[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
+/// How the current piece was last moved down.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub enum DropKind {
+    /// Moved down by gravity only.
+    #[default]
+    Gravity,
+    /// Moved down by the player one row at a time, the piece can still be moved afterwards.
+    Soft,
+    /// Dropped all the way down by the player, which locks the piece immediately.
+    Hard,
+}
+
+/// The number of times moving a piece on the ground can reset its lock delay, like the guideline games.
+const DEFAULT_LOCK_RESETS: u64 = 15;
+
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
-#[derive(Default)]
 pub struct Game {
     state: State,
     current_move: Option<Move>,
+    /// The position the current piece was rotated from, if the last action was a rotation.
+    rotated_from: Option<Position>,
+    seed: Option<u64>,
+    rng: GameRng,
+    randomizer: Box<dyn Randomizer>,
+    preview: usize,
+    hold_used: bool,
+    rotate_180: bool,
+    /// The number of ticks a piece can rest on the ground before it locks.
+    lock_delay: u64,
+    /// The number of times moving or rotating a piece on the ground can reset the lock delay.
+    lock_resets: u64,
+    /// The number of ticks the current piece has been resting on the ground.
+    lock_ticks: u64,
+    /// The number of lock delay resets used since the current piece reached its lowest row.
+    resets_used: u64,
+    /// The lowest row the current piece has reached.
+    lowest_row: isize,
+    drop_kind: DropKind,
+    board_size: BoardSize,
+    rotation_system: &'static dyn RotationSystem,
+    scoring: ScoringKind,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl Game {
+    /// Sets the randomizer that generates the pieces, this resets the game.
+    pub fn set_randomizer(&mut self, randomizer: Box<dyn Randomizer>) {
+        self.randomizer = randomizer;
+        self.reset();
+    }
+
+    /// Sets the size of the board, this resets the game.
+    pub fn set_board_size(&mut self, board_size: BoardSize) {
+        self.board_size = board_size;
+        self.reset();
+    }
+
+    /// Sets the rotation system of the pieces, this resets the game.
+    pub fn set_rotation_system(&mut self, rotation_system: &'static dyn RotationSystem) {
+        self.rotation_system = rotation_system;
+        self.reset();
+    }
+
+    /// Sets how points are awarded for clearing rows, this resets the game.
+    pub fn set_scoring(&mut self, scoring: ScoringKind) {
+        self.scoring = scoring;
+        self.reset();
+    }
 }
 
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl Game {
+    /// Creates a new game, if a seed is given the sequence of pieces is reproducible.
     #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
-    pub fn new() -> Self {
-        Self::default()
+    pub fn new(seed: Option<u64>) -> Self {
+        Self {
+            state: State::default(),
+            current_move: None,
+            rotated_from: None,
+            seed,
+            rng: seeded_rng(seed),
+            randomizer: RandomizerKind::default().build(),
+            preview: 0,
+            hold_used: false,
+            rotate_180: false,
+            lock_delay: 0,
+            lock_resets: DEFAULT_LOCK_RESETS,
+            lock_ticks: 0,
+            resets_used: 0,
+            lowest_row: 0,
+            drop_kind: DropKind::default(),
+            board_size: BoardSize::default(),
+            rotation_system: &Nes,
+            scoring: ScoringKind::default(),
+        }
     }
 
     pub fn reset(&mut self) {
-        self.state = State::default();
+        self.state = State::new(Board::new(self.board_size))
+            .with_rotation_system(self.rotation_system)
+            .with_scoring(self.scoring)
+            .with_preview(self.preview);
         self.current_move = None;
+        self.rotated_from = None;
+        self.drop_kind = DropKind::default();
+        self.hold_used = false;
+        self.rng = seeded_rng(self.seed);
+        self.randomizer.reset();
+    }
+
+    /// Sets the number of upcoming pieces that are previewed (0 to 6), this resets the game.
+    pub fn set_preview(&mut self, preview: usize) {
+        assert!(
+            preview <= MAX_PREVIEW,
+            "preview must be at most {MAX_PREVIEW}"
+        );
+        self.preview = preview;
+        self.reset();
+    }
+
+    /// Enables or disables rotating the piece by 180 degrees, which is not allowed by default.
+    pub fn set_rotate_180(&mut self, enabled: bool) {
+        self.rotate_180 = enabled;
+    }
+
+    /// Sets the number of ticks a piece can rest on the ground before it locks.
+    /// The default of 0 locks a piece as soon as it cannot move down, like NES Tetris.
+    pub fn set_lock_delay(&mut self, ticks: u64) {
+        self.lock_delay = ticks;
+    }
+
+    /// Sets how many times moving or rotating a piece on the ground can reset the lock delay (15 by default).
+    /// The count is restored when the piece reaches a row lower than before.
+    pub fn set_lock_resets(&mut self, resets: u64) {
+        self.lock_resets = resets;
+    }
+
+    /// How the current piece was last moved down.
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn drop_kind(&self) -> DropKind {
+        self.drop_kind
+    }
+
+    /// Selects a randomizer by name, see [`RandomizerKind`] for the available names.
+    pub fn select_randomizer(&mut self, name: &str) {
+        let Ok(kind) = name.parse::<RandomizerKind>() else {
+            panic!("Unknown randomizer: '{}'", name);
+        };
+        self.set_randomizer(kind.build());
+    }
+
+    /// Selects a board size by name, e.g. `10x20` or `10x20+20`, see [`BoardSize`].
+    pub fn select_board_size(&mut self, name: &str) {
+        let Ok(board_size) = name.parse::<BoardSize>() else {
+            panic!("Unknown board size: '{}'", name);
+        };
+        self.set_board_size(board_size);
+    }
+
+    /// Selects a rotation system by name, see [`RotationSystemKind`] for the available names.
+    pub fn select_rotation_system(&mut self, name: &str) {
+        let Ok(kind) = name.parse::<RotationSystemKind>() else {
+            panic!("Unknown rotation system: '{}'", name);
+        };
+        self.set_rotation_system(kind.system());
+    }
+
+    /// Selects a scoring ruleset by name, see [`ScoringKind`] for the available names.
+    pub fn select_scoring(&mut self, name: &str) {
+        let Ok(scoring) = name.parse::<ScoringKind>() else {
+            panic!("Unknown scoring: '{}'", name);
+        };
+        self.set_scoring(scoring);
     }
 
     pub fn step(&mut self) -> bool {
+        if self.state.game_over() {
+            return false;
+        }
         if let Some(current_move) = self.current_move {
             // move the current piece down
-            let next_move = current_move.drop(self.state.board());
-            if next_move.is_none() {
-                // if the piece is dropped, update the state
-                self.state = self.state.future(current_move);
+            if let Some(next_move) = current_move.drop(self.state.board()) {
+                self.descend(next_move, DropKind::Gravity);
+            } else if self.lock_ticks >= self.lock_delay || self.resets_used >= self.lock_resets {
+                self.lock();
+            } else {
+                self.lock_ticks += 1;
             }
-            self.current_move = next_move;
+            true
         } else {
             // spawn the next piece
-            let piece = gen_random_piece(self.state.delta().map(|d| d.r#move.piece.index()));
-            let start_move = piece.into_start_move();
-            if start_move.is_valid(self.state.board()) {
-                self.current_move = Some(start_move);
-            } else {
-                self.state.set_game_over();
-                return false;
-            }
+            let piece = self.state.next_piece(&mut *self.randomizer, &mut self.rng);
+            self.spawn(piece)
+        }
+    }
+
+    /// Swaps the current piece with the held piece, or with the next piece if nothing is held.
+    /// This can only be done once until the piece is dropped.
+    pub fn hold(&mut self) {
+        let Some(current_move) = self.current_move else {
+            return;
+        };
+        if self.hold_used {
+            return;
+        }
+        self.hold_used = true;
+        let piece = match self.state.swap_hold(current_move.piece) {
+            Some(held) => held,
+            None => self.state.next_piece(&mut *self.randomizer, &mut self.rng),
+        };
+        self.spawn(piece);
+    }
+
+    /// Places the current piece and updates the state.
+    fn lock(&mut self) {
+        if let Some(current_move) = self.current_move.take() {
+            self.state = self.state.future(current_move, self.rotated_from);
+            self.hold_used = false;
+            self.rotated_from = None;
+        }
+    }
+
+    /// Moves the current piece one row down.
+    fn descend(&mut self, next_move: Move, drop_kind: DropKind) {
+        self.current_move = Some(next_move);
+        self.rotated_from = None;
+        self.drop_kind = drop_kind;
+        self.lock_ticks = 0;
+        if next_move.pos.row > self.lowest_row {
+            self.lowest_row = next_move.pos.row;
+            self.resets_used = 0;
+        }
+    }
+
+    /// Restarts the lock delay after the piece was moved or rotated, if there are resets left.
+    fn reset_lock_delay(&mut self) {
+        if self.lock_ticks > 0 && self.resets_used < self.lock_resets {
+            self.lock_ticks = 0;
+            self.resets_used += 1;
+        }
+    }
+
+    fn spawn(&mut self, piece: Piece) -> bool {
+        let start_move = piece.into_start_move(self.state.board().size());
+        self.rotated_from = None;
+        self.drop_kind = DropKind::default();
+        self.lock_ticks = 0;
+        self.resets_used = 0;
+        self.lowest_row = start_move.pos.row;
+        if start_move.is_valid(self.state.board()) {
+            self.current_move = Some(start_move);
+            true
+        } else {
+            self.current_move = None;
+            self.state.set_game_over();
+            false
         }
-        true
     }
 
     #[inline]
     fn try_move(&mut self, move_change: impl FnOnce(Move) -> Move) {
-        if let Some(next_move) = self.current_move.map(move_change) {
-            if next_move.is_valid(self.state.board()) {
-                self.current_move = Some(next_move);
-            }
+        if let Some(next_move) = self.current_move.map(move_change)
+            && next_move.is_valid(self.state.board())
+        {
+            self.current_move = Some(next_move);
+            self.rotated_from = None;
+            self.reset_lock_delay();
         }
     }
 
@@ -70,24 +301,58 @@ impl Game {
     }
 
     pub fn soft_drop(&mut self) {
-        self.try_move(|mut m| {
-            m.pos.row += 1;
-            m
-        });
+        if let Some(next_move) = self.current_move.and_then(|m| m.drop(self.state.board())) {
+            self.descend(next_move, DropKind::Soft);
+            self.state.add_drop_points(1, false);
+        }
     }
 
+    /// Drops the current piece all the way down and locks it without waiting for the lock delay.
     pub fn hard_drop(&mut self) {
+        if self.current_move.is_none() {
+            return;
+        }
+        let mut rows = 0;
         while let Some(next_move) = self.current_move.and_then(|m| m.drop(self.state.board())) {
-            self.current_move = Some(next_move);
+            self.descend(next_move, DropKind::Hard);
+            rows += 1;
         }
-        self.step(); // don't waste the next tick doing nothing
+        self.drop_kind = DropKind::Hard;
+        self.state.add_drop_points(rows, true);
+        self.lock(); // don't waste the next tick doing nothing
     }
 
+    /// Rotates the current piece by the given number of clockwise quarter turns.
+    fn try_rotate(&mut self, turns: usize) {
+        if let Some(current_move) = self.current_move {
+            let rotations = current_move.piece.num_rotations();
+            let rot = (current_move.pos.rot + turns) % rotations;
+            if rot == current_move.pos.rot {
+                return;
+            }
+            if let Some(rotated) = current_move.rotate(self.state.board(), rot) {
+                self.current_move = Some(rotated);
+                self.rotated_from = Some(current_move.pos);
+                self.reset_lock_delay();
+            }
+        }
+    }
+
+    /// Rotates the current piece clockwise.
     pub fn rotate(&mut self) {
-        self.try_move(|mut m| {
-            m.pos.rot = (m.pos.rot + 1) % m.piece.num_rotations();
-            m
-        })
+        self.try_rotate(1);
+    }
+
+    /// Rotates the current piece counter-clockwise.
+    pub fn rotate_ccw(&mut self) {
+        self.try_rotate(MAX_ROTATIONS - 1);
+    }
+
+    /// Rotates the current piece by 180 degrees, if enabled with [`Game::set_rotate_180`].
+    pub fn rotate_180(&mut self) {
+        if self.rotate_180 {
+            self.try_rotate(2);
+        }
     }
 
     #[cfg(feature = "wasm")]
@@ -102,3 +367,67 @@ impl Game {
         self.current_move
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Steps the game until the current piece rests on the ground.
+    fn land(game: &mut Game) {
+        while let Some(current_move) = game.current_move {
+            if current_move.drop(game.state.board()).is_none() {
+                return;
+            }
+            game.step();
+        }
+    }
+
+    #[test]
+    fn test_lock_delay() {
+        let mut game = Game::new(Some(0));
+        game.set_lock_delay(2);
+        game.step();
+        land(&mut game);
+        assert_eq!(game.drop_kind(), DropKind::Gravity);
+        game.step();
+        game.step();
+        assert!(game.current_move.is_some());
+        // moving the piece restarts the lock delay
+        game.move_left();
+        game.step();
+        game.step();
+        assert!(game.current_move.is_some());
+        game.step();
+        assert!(game.current_move.is_none());
+        assert_eq!(game.state.stats().steps, 1);
+    }
+
+    #[test]
+    fn test_lock_resets() {
+        let mut game = Game::new(Some(0));
+        game.set_lock_delay(1);
+        game.set_lock_resets(2);
+        game.step();
+        land(&mut game);
+        for _ in 0..2 {
+            game.step();
+            game.rotate();
+        }
+        // the resets are used up, so the piece locks on the next tick
+        game.step();
+        assert!(game.current_move.is_none());
+    }
+
+    #[test]
+    fn test_hard_drop_locks() {
+        let mut game = Game::new(Some(0));
+        game.set_lock_delay(10);
+        game.step();
+        game.soft_drop();
+        assert_eq!(game.drop_kind(), DropKind::Soft);
+        game.hard_drop();
+        assert_eq!(game.drop_kind(), DropKind::Hard);
+        assert!(game.current_move.is_none());
+        assert_eq!(game.state.stats().steps, 1);
+    }
+}
@@ -1,20 +1,25 @@
 pub mod board;
+pub mod cache;
 pub mod feature;
 pub mod game;
 pub mod r#move;
 pub mod piece;
+pub mod policy;
+pub mod queue;
 pub mod rng;
+pub mod rotation;
+pub mod scoring;
+pub mod search;
 pub mod simulator;
 pub mod state;
 #[cfg(test)]
 pub mod test;
+pub mod timing;
 pub mod train;
-
-#[cfg(feature = "wasm")]
-use wasm_bindgen::prelude::*;
+pub mod tspin;
 
 #[cfg(all(target_arch = "wasm32", feature = "wasm"))]
-#[wasm_bindgen(start)]
+#[wasm_bindgen::prelude::wasm_bindgen(start)]
 pub fn wasm_main() {
     console_error_panic_hook::set_once();
 }
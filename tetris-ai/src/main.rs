@@ -1,29 +1,187 @@
 use std::time::Instant;
+#[cfg(feature = "count-allocations")]
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicU64, Ordering},
+};
 use tetris_ai::{
+    board::BoardSize,
     feature::Features,
+    queue::MAX_PREVIEW,
+    rng::RandomizerKind,
+    rotation::RotationSystemKind,
+    scoring::ScoringKind,
+    search::SearchMode,
     simulator::Simulator,
+    timing::Timing,
     train::{TrainCriterion, Trainer},
 };
 
+/// Counts the allocations, so that the benchmark can show how often searching allocates.
+#[cfg(feature = "count-allocations")]
+struct CountingAllocator;
+
+#[cfg(feature = "count-allocations")]
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+#[cfg(feature = "count-allocations")]
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc_zeroed(layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[cfg(feature = "count-allocations")]
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Returns the number of allocations so far, `None` if they are not counted.
+fn allocations() -> Option<u64> {
+    #[cfg(feature = "count-allocations")]
+    return Some(ALLOCATIONS.load(Ordering::Relaxed));
+    #[cfg(not(feature = "count-allocations"))]
+    None
+}
+
 fn main() {
     let args = std::env::args().collect::<Vec<_>>();
 
     if args.len() > 2 {
         let arg2 = args[2].as_str();
+        let options = match Options::parse(&args[3..]) {
+            Ok(options) => options,
+            Err(err) => {
+                eprintln!("{}", err);
+                return;
+            }
+        };
         match args[1].as_str() {
-            "run" => run(arg2),
-            "train" => train(arg2),
+            "run" => run(arg2, options),
+            "train" => train(arg2, options),
+            "bench" => bench(arg2, options),
             _ => eprintln!("Unknown command: {}", args[1]),
         }
     } else {
-        eprintln!("Usage: {} run|train [preset|criterion]", args[0]);
+        eprintln!(
+            "Usage: {} run|train|bench [preset|criterion] [--seed <seed>] [--randomizer <randomizer>] [--preview <n>] [--search <mode>] [--board <size>] [--rotation <system>] [--scoring <scoring>] [--timing <timing>] [--rotate-180] [--incremental]",
+            args[0]
+        );
     }
 }
 
-fn run(preset: &str) {
+/// Options that can be passed to the commands as `--name value`.
+struct Options {
+    seed: Option<u64>,
+    randomizer: RandomizerKind,
+    preview: usize,
+    search: SearchMode,
+    board_size: BoardSize,
+    rotation_system: RotationSystemKind,
+    scoring: ScoringKind,
+    timing: Timing,
+    rotate_180: bool,
+    incremental: bool,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let seed = option(args, "--seed")
+            .map(str::parse)
+            .transpose()
+            .map_err(|_| "Invalid seed, expected an unsigned integer".to_string())?;
+        let randomizer = option(args, "--randomizer").unwrap_or("nes");
+        let randomizer = randomizer
+            .parse()
+            .map_err(|_| format!("Unknown randomizer: '{}'", randomizer))?;
+        let preview = option(args, "--preview")
+            .unwrap_or("0")
+            .parse()
+            .ok()
+            .filter(|&preview| preview <= MAX_PREVIEW)
+            .ok_or_else(|| format!("Invalid preview, expected 0 to {}", MAX_PREVIEW))?;
+        let search = option(args, "--search").unwrap_or("greedy");
+        let search = search
+            .parse()
+            .map_err(|_| format!("Unknown search mode: '{}'", search))?;
+        let board_size = option(args, "--board").unwrap_or("10x20");
+        let board_size = board_size
+            .parse()
+            .map_err(|_| format!("Invalid board size: '{}'", board_size))?;
+        let rotation_system = option(args, "--rotation").unwrap_or("nes");
+        let rotation_system = rotation_system
+            .parse()
+            .map_err(|_| format!("Unknown rotation system: '{}'", rotation_system))?;
+        let scoring = option(args, "--scoring").unwrap_or("nes");
+        let scoring = scoring
+            .parse()
+            .map_err(|_| format!("Unknown scoring: '{}'", scoring))?;
+        let timing = option(args, "--timing").unwrap_or("ticks");
+        let timing = timing
+            .parse()
+            .map_err(|_| format!("Unknown timing: '{}'", timing))?;
+        let rotate_180 = args.iter().any(|arg| arg == "--rotate-180");
+        let incremental = args.iter().any(|arg| arg == "--incremental");
+        Ok(Self {
+            seed,
+            randomizer,
+            preview,
+            search,
+            board_size,
+            rotation_system,
+            scoring,
+            timing,
+            rotate_180,
+            incremental,
+        })
+    }
+}
+
+/// Finds the value of an option given as `--name value`.
+fn option<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|i| args.get(i + 1))
+        .map(String::as_str)
+}
+
+fn simulator(preset: &str, seed: Option<u64>, options: &Options) -> Simulator {
+    let mut simulator = Simulator::new_with_preset(preset, seed);
+    simulator.set_randomizer(options.randomizer.build());
+    simulator.set_board_size(options.board_size);
+    simulator.set_rotation_system(options.rotation_system.system());
+    simulator.set_scoring(options.scoring);
+    simulator.set_timing(options.timing);
+    simulator.set_rotate_180(options.rotate_180);
+    simulator.set_incremental(options.incremental);
+    simulator.set_preview(options.preview);
+    simulator.set_search(options.search);
+    simulator
+}
+
+fn run(preset: &str, options: Options) {
     let start = Instant::now();
-    let mut simulator = Simulator::new_with_preset(preset);
-    simulator.run();
+    let mut simulator = simulator(preset, options.seed, &options);
+    let (mut nodes, mut micros) = (0, 0);
+    while simulator.step() {
+        let search_stats = simulator.search_stats();
+        nodes += search_stats.nodes;
+        micros += search_stats.micros;
+    }
     let elapsed = start.elapsed();
     let stats = simulator.stats();
     println!(
@@ -35,10 +193,62 @@ fn run(preset: &str) {
         elapsed.as_secs_f64(),
         stats.steps as f64 / elapsed.as_secs_f64()
     );
+    let steps = stats.steps.max(1) as f64;
+    println!(
+        "nodes/move: {:.0}, search time/move: {:.2}ms",
+        nodes as f64 / steps,
+        micros as f64 / steps / 1000.0
+    );
     println!("{}", simulator.board());
 }
 
-fn train(criterion: &str) {
+/// Plays the first pieces of a number of games, starting at the given seed,
+/// and reports the speed of the search and how often it allocates.
+fn bench(preset: &str, options: Options) {
+    const GAMES: u64 = 20;
+    const PIECES: usize = 500;
+    let first_seed = options.seed.unwrap_or(0);
+    let (mut moves, mut nodes, mut micros, mut allocated) = (0, 0, 0, Some(0));
+    let start = Instant::now();
+    for seed in first_seed..first_seed + GAMES {
+        let mut simulator = simulator(preset, Some(seed), &options);
+        // the buffers grow during the first pieces, after that only the chosen paths are allocated
+        let before = allocations();
+        for _ in 0..PIECES {
+            if !simulator.step() {
+                break;
+            }
+            let search_stats = simulator.search_stats();
+            nodes += search_stats.nodes;
+            micros += search_stats.micros;
+        }
+        allocated = allocated
+            .zip(before)
+            .zip(allocations())
+            .map(|((allocated, before), after)| allocated + after - before);
+        moves += simulator.stats().steps;
+    }
+    let elapsed = start.elapsed();
+    let moves_f = moves.max(1) as f64;
+    println!(
+        "games: {}, moves: {}, elapsed: {:.2}s, moves/sec: {:.0}",
+        GAMES,
+        moves,
+        elapsed.as_secs_f64(),
+        moves as f64 / elapsed.as_secs_f64()
+    );
+    print!(
+        "nodes/move: {:.0}, search time/move: {:.3}ms",
+        nodes as f64 / moves_f,
+        micros as f64 / moves_f / 1000.0
+    );
+    match allocated {
+        Some(allocated) => println!(", allocations/move: {:.2}", allocated as f64 / moves_f),
+        None => println!(),
+    }
+}
+
+fn train(criterion: &str, options: Options) {
     // The features to train on
     const FEATURE_NAMES: &[&str] = &[
         "col_trans",
@@ -53,7 +263,13 @@ fn train(criterion: &str) {
         return;
     };
 
-    let mut trainer = Trainer::new(Features::from_names(FEATURE_NAMES), criterion);
+    let mut trainer = Trainer::new(Features::from_names(FEATURE_NAMES), criterion, options.seed);
+    trainer.set_randomizer(options.randomizer);
+    trainer.set_board_size(options.board_size);
+    trainer.set_rotation_system(options.rotation_system.system());
+    trainer.set_scoring(options.scoring);
+    trainer.set_timing(options.timing);
+    trainer.set_rotate_180(options.rotate_180);
     while !trainer.is_stable() {
         let state = trainer.step();
         println!(
@@ -1,9 +1,10 @@
-use crate::board::Board;
+use crate::board::{Board, MAX_BOARD_HEIGHT, MAX_BOARD_WIDTH};
 #[cfg(feature = "wasm")]
 use crate::piece::WasmPattern;
-use crate::piece::{Pattern, Piece};
+use crate::piece::{MAX_PATTERN_SIZE, MAX_ROTATIONS, Pattern, Piece};
+use crate::timing::{FrameSearch, Timing};
 use serde::Serialize;
-use std::collections::{BinaryHeap, HashMap};
+use std::collections::BinaryHeap;
 #[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
@@ -31,6 +32,12 @@ impl Move {
             None
         }
     }
+
+    /// Rotates the piece to the given rotation using the rotation system of the piece.
+    /// Returns `None` if the rotation is blocked, even after trying the kicks.
+    pub fn rotate(self, board: &Board, rot: usize) -> Option<Move> {
+        self.piece.rotation_system().rotate(board, self, rot)
+    }
 }
 
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
@@ -49,11 +56,50 @@ pub struct Position {
     pub col: isize,
 }
 
+/// How a piece reaches its landing position.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub enum Placement {
+    /// The piece can be dropped straight down from above the stack.
+    #[default]
+    HardDrop,
+    /// The piece is soft dropped and then shifted under an overhang.
+    Tuck,
+    /// The piece is soft dropped and then rotated under an overhang.
+    Spin,
+}
+
+impl Placement {
+    /// Classifies a landing position by whether anything blocks the piece above it.
+    /// `rotated_from` is the position the piece was rotated from, if the last action was a rotation.
+    pub fn classify(board: &Board, r#move: Move, rotated_from: Option<Position>) -> Placement {
+        // nothing can block the piece above the highest filled cell
+        let max_height = board.heights().iter().copied().max().unwrap_or(0);
+        let stack_top = (board.size().height - max_height) as isize;
+        let rows = r#move.pattern().rows() as isize;
+        let mut above = r#move;
+        while above.pos.row + rows > stack_top {
+            above.pos.row -= 1;
+            if board.overlaps_move(above) {
+                return if rotated_from.is_some() {
+                    Placement::Spin
+                } else {
+                    Placement::Tuck
+                };
+            }
+        }
+        Placement::HardDrop
+    }
+}
+
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
 pub struct Path {
     piece: Piece,
     positions: Vec<Vec<Position>>,
+    /// The number of frames the placement takes, when generated with a frame-exact timing.
+    frames: Option<u64>,
+    placement: Placement,
 }
 
 impl Path {
@@ -76,6 +122,8 @@ impl Path {
         Self {
             piece,
             positions: moves_per_tick,
+            frames: None,
+            placement: Placement::default(),
         }
     }
 
@@ -95,19 +143,48 @@ impl Path {
         }
     }
 
+    /// Returns the position the piece was rotated from, if the last action on the path was a rotation.
+    pub fn rotated_from(&self) -> Option<Position> {
+        let mut positions = self
+            .positions
+            .iter()
+            .rev()
+            .flat_map(|tick| tick.iter().rev());
+        let last = positions.next()?;
+        // with a frame-exact timing, the piece can stay in the same position for several ticks
+        positions
+            .find(|&previous| previous != last)
+            .copied()
+            .filter(|previous| previous.rot != last.rot)
+    }
+
+    /// Returns the number of frames from spawning the piece until the next piece spawns,
+    /// if the path was generated with a frame-exact timing, see [`Timing`].
+    /// Each tick of the path is then a single frame.
+    pub fn frames(&self) -> Option<u64> {
+        self.frames
+    }
+
     pub fn into_moves(self) -> Vec<Vec<Position>> {
         self.positions
     }
 }
 
-#[cfg(feature = "wasm")]
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl Path {
+    /// Returns how the piece reaches the end of the path, see [`Placement`].
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn placement(&self) -> Placement {
+        self.placement
+    }
+
+    #[cfg(feature = "wasm")]
     #[wasm_bindgen(getter)]
     pub fn length(&self) -> usize {
         self.positions.len()
     }
 
+    #[cfg(feature = "wasm")]
     pub fn transition_move(&self, index: usize, progress: f64) -> Move {
         let curr = &self.positions[index];
         let idx = ((progress * curr.len() as f64).floor() as usize).min(curr.len() - 1);
@@ -143,42 +220,60 @@ impl PartialOrd for Node {
     }
 }
 
-fn next_positions(candidates: &mut [Position], pos: Position, piece: Piece) -> usize {
-    candidates[0] = Position {
+/// Finds the positions that can be reached from a position in a single move and do not overlap
+/// with the board. Rotations are kicked by the rotation system of the piece.
+fn next_positions(
+    candidates: &mut [Position],
+    pos: Position,
+    piece: Piece,
+    board: &Board,
+    rotate_180: bool,
+) -> usize {
+    let mut count = 0;
+    let mut push = |next: Position| {
+        if !board.overlaps_move(Move { piece, pos: next }) {
+            candidates[count] = next;
+            count += 1;
+        }
+    };
+    push(Position {
         rot: pos.rot,
         row: pos.row,
         col: pos.col - 1, // left
-    };
-    candidates[1] = Position {
+    });
+    push(Position {
         rot: pos.rot,
         row: pos.row,
         col: pos.col + 1, // right
-    };
-    candidates[2] = Position {
+    });
+    push(Position {
         rot: pos.rot,
         row: pos.row + 1, // down
         col: pos.col,
-    };
+    });
     // Rotate
-    if piece.num_rotations() > 1 {
-        candidates[3] = Position {
-            rot: (pos.rot + 1) % piece.num_rotations(),
-            row: pos.row,
-            col: pos.col,
-        };
-        if piece.num_rotations() > 2 {
-            candidates[4] = Position {
-                rot: pos.rot.wrapping_sub(1) % piece.num_rotations(),
-                row: pos.row,
-                col: pos.col,
-            };
-            5
-        } else {
-            4
+    let current = Move { piece, pos };
+    let rotations = piece.num_rotations();
+    if rotations > 1 {
+        if let Some(rotated) = current.rotate(board, (pos.rot + 1) % rotations) {
+            candidates[count] = rotated.pos;
+            count += 1;
+        }
+        if rotations > 2
+            && let Some(rotated) = current.rotate(board, (pos.rot + rotations - 1) % rotations)
+        {
+            candidates[count] = rotated.pos;
+            count += 1;
+        }
+        if rotate_180
+            && rotations > 2
+            && let Some(rotated) = current.rotate(board, (pos.rot + 2) % rotations)
+        {
+            candidates[count] = rotated.pos;
+            count += 1;
         }
-    } else {
-        3
     }
+    count
 }
 
 fn touches_ground(piece: Piece, pos: Position, board: &Board) -> bool {
@@ -192,87 +287,404 @@ fn touches_ground(piece: Piece, pos: Position, board: &Board) -> bool {
     })
 }
 
-/// Calculates all paths to all possible landing positions for a given piece using Dijkstra's algorithm.
-/// `time_pressure_level` is used the simulate time pressure as a Tetris game speeds up, this will
-/// limit the number of moves and the number of moves per tick, making the AI seem more realistic.
-pub fn move_dijkstra(board: &Board, piece: Piece, time_pressure_level: Option<u64>) -> Vec<Path> {
-    let mut cost = HashMap::<Position, u64>::new();
-    let mut parent = HashMap::<Position, Position>::new();
-    let mut to_visit = BinaryHeap::new();
-    let mut destinations = Vec::new();
-
-    let (max_moves, max_tick_moves) = if let Some(time_pressure_level) = time_pressure_level {
-        let speed: f64 = time_pressure_level as f64 / 30.0; // speed cap at level 30
-        // linearly decrease with speed
-        let max_moves = MIN_MOVES + ((1.0 - speed) * (MAX_MOVES - MIN_MOVES) as f64).round() as u64;
-        let max_tick_moves = 1 + ((1.0 - speed) * (MAX_MOVES_PER_TICK - 1) as f64).round() as u64;
-        (max_moves, max_tick_moves)
-    } else {
-        (MAX_MOVES, MAX_MOVES_PER_TICK)
-    };
+/// Positions are stored in arrays indexed by rotation, row and column.
+/// Patterns can stick out of the board at the top and sides by their empty rows and columns.
+const INDEX_OFFSET: isize = MAX_PATTERN_SIZE as isize;
+const INDEX_SIZE: usize = MAX_ROTATIONS
+    * (MAX_BOARD_HEIGHT + MAX_PATTERN_SIZE)
+    * (MAX_BOARD_WIDTH + 2 * MAX_PATTERN_SIZE);
+const UNVISITED: u16 = u16::MAX;
+
+/// Finds all landing positions of a piece using Dijkstra's algorithm.
+/// The buffers are reused between calls, so that no memory is allocated after the first call.
+/// Only the path to a chosen destination is materialized, see [`MoveGenerator::path`].
+#[derive(Clone, Debug)]
+pub struct MoveGenerator {
+    cost: Vec<u16>,
+    parent: Vec<Position>,
+    to_visit: BinaryHeap<Node>,
+    destinations: Vec<Position>,
+    piece: Option<Piece>,
+    start: Position,
+    /// The board of the last call to [`MoveGenerator::generate`], used to classify placements.
+    board: Board,
+    /// Whether pieces can be rotated by 180 degrees, which is not allowed by default.
+    rotate_180: bool,
+    timing: Timing,
+    frame_search: FrameSearch,
+    /// The number of rows and columns of the index, depending on the size of the board.
+    index_rows: usize,
+    index_cols: usize,
+}
 
-    let start_move = piece.into_start_move();
-    if board.overlaps_move(start_move) {
-        return Vec::new();
+impl Default for MoveGenerator {
+    fn default() -> Self {
+        Self {
+            cost: vec![UNVISITED; INDEX_SIZE],
+            parent: vec![Position::default(); INDEX_SIZE],
+            to_visit: BinaryHeap::new(),
+            destinations: Vec::new(),
+            piece: None,
+            start: Position::default(),
+            board: Board::default(),
+            rotate_180: false,
+            timing: Timing::default(),
+            frame_search: FrameSearch::default(),
+            index_rows: 0,
+            index_cols: 0,
+        }
+    }
+}
+
+impl MoveGenerator {
+    pub fn new() -> Self {
+        Self::default()
     }
-    cost.insert(start_move.pos, 0);
-    to_visit.push(Node {
-        pos: start_move.pos,
-        cost: 0,
-        moves: 0,
-        tick_moves: 0,
-    });
 
-    let mut candidates = [Position::default(); 5];
-
-    while let Some(Node {
-        pos: current,
-        cost: current_cost,
-        moves: current_moves,
-        tick_moves: current_tick_moves,
-    }) = to_visit.pop()
-    {
-        if touches_ground(piece, current, board) {
-            destinations.push(current);
+    /// Enables or disables 180 degree rotations when generating moves.
+    pub fn set_rotate_180(&mut self, enabled: bool) {
+        self.rotate_180 = enabled;
+    }
+
+    /// Sets how the time it takes to move the piece is modelled.
+    pub fn set_timing(&mut self, timing: Timing) {
+        self.timing = timing;
+    }
+
+    #[inline]
+    fn index(&self, pos: Position) -> usize {
+        debug_assert!(pos.rot < MAX_ROTATIONS);
+        let row = (pos.row + INDEX_OFFSET) as usize;
+        let col = (pos.col + INDEX_OFFSET) as usize;
+        debug_assert!(row < self.index_rows && col < self.index_cols);
+        (pos.rot * self.index_rows + row) * self.index_cols + col
+    }
+
+    /// Calculates all landing positions for a given piece, which are available from
+    /// [`MoveGenerator::destinations`] until the next call.
+    /// `time_pressure_level` is used the simulate time pressure as a Tetris game speeds up, this will
+    /// limit the number of moves and the number of moves per tick, making the AI seem more realistic.
+    /// With a frame-exact timing, it is the level that sets the gravity, or level 0 if not given.
+    pub fn generate(&mut self, board: &Board, piece: Piece, time_pressure_level: Option<u64>) {
+        self.board = *board;
+        if let Timing::Nes(timing) = self.timing {
+            let start_move = piece.into_start_move(board.size());
+            self.piece = Some(piece);
+            self.start = start_move.pos;
+            self.destinations.clear();
+            self.frame_search.generate(
+                board,
+                start_move,
+                time_pressure_level.unwrap_or(0),
+                &timing,
+                self.rotate_180,
+                &mut self.destinations,
+            );
+            return;
         }
-        let next_count = next_positions(&mut candidates, current, piece);
-        for next in candidates[..next_count].iter().copied() {
-            if board.overlaps_move(Move { piece, pos: next }) {
-                continue;
+        let size = board.size();
+        self.index_rows = size.height + MAX_PATTERN_SIZE;
+        self.index_cols = size.width + 2 * MAX_PATTERN_SIZE;
+        self.cost[..MAX_ROTATIONS * self.index_rows * self.index_cols].fill(UNVISITED);
+        self.to_visit.clear();
+        self.destinations.clear();
+        self.piece = Some(piece);
+
+        let (max_moves, max_tick_moves) = if let Some(time_pressure_level) = time_pressure_level {
+            let speed: f64 = time_pressure_level as f64 / 30.0; // speed cap at level 30
+            // linearly decrease with speed
+            let max_moves =
+                MIN_MOVES + ((1.0 - speed) * (MAX_MOVES - MIN_MOVES) as f64).round() as u64;
+            let max_tick_moves =
+                1 + ((1.0 - speed) * (MAX_MOVES_PER_TICK - 1) as f64).round() as u64;
+            (max_moves, max_tick_moves)
+        } else {
+            (MAX_MOVES, MAX_MOVES_PER_TICK)
+        };
+
+        let start_move = piece.into_start_move(size);
+        self.start = start_move.pos;
+        if board.overlaps_move(start_move) {
+            return;
+        }
+        let start_index = self.index(start_move.pos);
+        self.cost[start_index] = 0;
+        self.to_visit.push(Node {
+            pos: start_move.pos,
+            cost: 0,
+            moves: 0,
+            tick_moves: 0,
+        });
+
+        let mut candidates = [Position::default(); 6];
+
+        while let Some(Node {
+            pos: current,
+            cost: current_cost,
+            moves: current_moves,
+            tick_moves: current_tick_moves,
+        }) = self.to_visit.pop()
+        {
+            if current_cost > self.cost[self.index(current)] as u64 {
+                continue; // already visited with a lower cost, e.g. after a kick
             }
-            // staying on the same row, i.e. the same tick does not cost anything
-            // but is limited by max_moves and max_tick_moves
-            let (new_cost, new_moves, new_tick_moves) = if next.row == current.row {
-                (current_cost, current_moves + 1, current_tick_moves + 1)
-            } else {
-                (current_cost + 1, current_moves, 0)
+            if touches_ground(piece, current, board) {
+                self.destinations.push(current);
+            }
+            let next_count =
+                next_positions(&mut candidates, current, piece, board, self.rotate_180);
+            for next in candidates[..next_count].iter().copied() {
+                if next.row < -INDEX_OFFSET {
+                    continue; // kicked too far above the board
+                }
+                // moves within the same tick do not cost anything
+                // but are limited by max_moves and max_tick_moves
+                let dropped = next.rot == current.rot && next.row > current.row;
+                let (new_cost, new_moves, new_tick_moves) = if dropped {
+                    (current_cost + 1, current_moves, 0)
+                } else {
+                    (current_cost, current_moves + 1, current_tick_moves + 1)
+                };
+                if new_moves > max_moves || new_tick_moves > max_tick_moves {
+                    continue;
+                }
+                let index = self.index(next);
+                if new_cost < self.cost[index] as u64 {
+                    self.cost[index] = new_cost as u16;
+                    self.to_visit.push(Node {
+                        pos: next,
+                        cost: new_cost,
+                        moves: new_moves,
+                        tick_moves: new_tick_moves,
+                    });
+                    self.parent[index] = current;
+                }
+            }
+        }
+    }
+
+    /// Returns the landing positions found by the last call to [`MoveGenerator::generate`].
+    pub fn destinations(&self) -> &[Position] {
+        &self.destinations
+    }
+
+    /// Returns the position the piece was rotated from, if the last action on the path to a landing
+    /// position was a rotation, without materializing the path.
+    pub fn rotated_from(&self, destination: Position) -> Option<Position> {
+        if let Timing::Nes(_) = self.timing {
+            return self.frame_search.rotated_from(destination);
+        }
+        if destination == self.start {
+            return None;
+        }
+        let previous = self.parent[self.index(destination)];
+        (previous.rot != destination.rot).then_some(previous)
+    }
+
+    /// Classifies how the piece reaches a landing position, see [`Placement`].
+    pub fn placement(&self, destination: Position) -> Placement {
+        let piece = self.piece.expect("no moves generated");
+        let r#move = Move {
+            piece,
+            pos: destination,
+        };
+        Placement::classify(&self.board, r#move, self.rotated_from(destination))
+    }
+
+    /// Returns the path to a landing position found by the last call to [`MoveGenerator::generate`].
+    pub fn path(&self, destination: Position) -> Path {
+        let piece = self.piece.expect("no moves generated");
+        if let Timing::Nes(timing) = self.timing {
+            return Path {
+                piece,
+                positions: self.frame_search.ticks(destination),
+                frames: Some(
+                    self.frame_search.frames(destination)
+                        + timing.entry_delay_after(
+                            Move {
+                                piece,
+                                pos: destination,
+                            },
+                            self.board.size().height,
+                        ),
+                ),
+                placement: self.placement(destination),
             };
-            if new_moves > max_moves || new_tick_moves > max_tick_moves {
-                continue;
+        }
+        debug_assert!(self.cost[self.index(destination)] != UNVISITED);
+        let mut current = destination;
+        let mut path = Vec::new();
+        while current != self.start {
+            path.push(current);
+            current = self.parent[self.index(current)];
+        }
+        path.push(self.start);
+        Path {
+            placement: self.placement(destination),
+            ..Path::from_path_reverse(path, piece)
+        }
+    }
+}
+
+/// Calculates all paths to all possible landing positions for a given piece using Dijkstra's algorithm.
+/// `time_pressure_level` is used the simulate time pressure as a Tetris game speeds up, this will
+/// limit the number of moves and the number of moves per tick, making the AI seem more realistic.
+/// This allocates a new [`MoveGenerator`], reuse one instead when generating moves repeatedly.
+pub fn move_dijkstra(board: &Board, piece: Piece, time_pressure_level: Option<u64>) -> Vec<Path> {
+    let mut generator = MoveGenerator::new();
+    generator.generate(board, piece, time_pressure_level);
+    generator
+        .destinations()
+        .iter()
+        .map(|&destination| generator.path(destination))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece::N_PIECES;
+    use crate::rotation::{Srs, SrsNoKicks};
+    use crate::test::random_board;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_reused_generator() {
+        let mut generator = MoveGenerator::new();
+        for _ in 0..50 {
+            let board = random_board();
+            for piece in (0..N_PIECES).map(Piece::from_index) {
+                generator.generate(&board, piece, None);
+                let paths = generator
+                    .destinations()
+                    .iter()
+                    .map(|&destination| generator.path(destination))
+                    .collect::<Vec<_>>();
+                assert_eq!(paths, move_dijkstra(&board, piece, None));
+                for path in paths {
+                    assert!(path.final_move().is_valid(&board));
+                }
             }
-            if !cost.contains_key(&next) || new_cost < cost[&next] {
-                cost.insert(next, new_cost);
-                to_visit.push(Node {
-                    pos: next,
-                    cost: new_cost,
-                    moves: new_moves,
-                    tick_moves: new_tick_moves,
-                });
-                parent.insert(next, current);
+        }
+    }
+
+    #[test]
+    fn test_rotate_180() {
+        let mut generator = MoveGenerator::new();
+        generator.set_rotate_180(true);
+        for _ in 0..20 {
+            let board = random_board();
+            for piece in (0..N_PIECES).map(|i| Piece::from_index(i).with_rotation_system(&Srs)) {
+                generator.generate(&board, piece, None);
+                for &destination in generator.destinations() {
+                    let path = generator.path(destination);
+                    assert_eq!(path.final_move().pos, destination);
+                    assert!(path.final_move().is_valid(&board));
+                }
             }
         }
     }
-    destinations
-        .into_iter()
-        .map(|end| {
-            let mut current = end;
-            let mut path = Vec::new();
-            while current != start_move.pos {
-                path.push(current);
-                current = parent[&current];
+
+    #[test]
+    fn test_kick_placement() {
+        let mut rows = vec![".........."; 16];
+        rows.extend(["....#.....", "#...#.....", "#..##..#.#", "##.##....#"]);
+        let board = Board::from_str(&rows.join("\n")).unwrap();
+        // the I piece can only reach the bottom row by kicking down from a vertical rotation
+        let piece = Piece::from_name('I').unwrap().with_rotation_system(&Srs);
+        let destination = Position {
+            rot: 2,
+            row: 17,
+            col: 5,
+        };
+        let mut generator = MoveGenerator::new();
+        generator.generate(&board, piece, None);
+        assert!(generator.destinations().contains(&destination));
+        let path = generator.path(destination);
+        assert_eq!(path.final_move().pos, destination);
+
+        // the same shapes without kicks cannot reach it
+        generator.generate(&board, piece.with_rotation_system(&SrsNoKicks), None);
+        assert!(!generator.destinations().contains(&destination));
+    }
+
+    #[test]
+    fn test_placement() {
+        let mut rows = vec![".........."; 17];
+        rows.extend(["####......", "#.........", "##.#######"]);
+        let board = Board::from_str(&rows.join("\n")).unwrap();
+        let mut generator = MoveGenerator::new();
+        let piece = Piece::from_name('I').unwrap().with_rotation_system(&Srs);
+        generator.generate(&board, piece, None);
+        // sliding under the overhang on the left
+        let tuck = Position {
+            rot: 0,
+            row: 17,
+            col: 1,
+        };
+        assert!(generator.destinations().contains(&tuck));
+        assert_eq!(generator.placement(tuck), Placement::Tuck);
+        assert_eq!(generator.path(tuck).placement(), Placement::Tuck);
+        let drop = Position {
+            rot: 0,
+            row: 17,
+            col: 6,
+        };
+        assert!(generator.destinations().contains(&drop));
+        assert_eq!(generator.placement(drop), Placement::HardDrop);
+
+        // the T piece rotated into the slot below the overhang
+        let piece = Piece::from_name('T').unwrap().with_rotation_system(&Srs);
+        let spin = Move {
+            piece,
+            pos: Position {
+                rot: 2,
+                row: 17,
+                col: 1,
+            },
+        };
+        assert!(spin.is_valid(&board));
+        let from = Position { rot: 1, ..spin.pos };
+        assert_eq!(
+            Placement::classify(&board, spin, Some(from)),
+            Placement::Spin
+        );
+        assert_eq!(Placement::classify(&board, spin, None), Placement::Tuck);
+    }
+
+    #[test]
+    fn test_wall_kick() {
+        let board = Board::default();
+        let piece = Piece::from_name('T').unwrap().with_rotation_system(&Srs);
+        // pointing right against the left wall, rotating in place would stick out of the board
+        let r#move = Move {
+            piece,
+            pos: Position {
+                rot: 1,
+                row: 10,
+                col: -1,
+            },
+        };
+        assert!(r#move.is_valid(&board));
+        let rotated = r#move.rotate(&board, 2).unwrap();
+        assert_eq!(
+            rotated.pos,
+            Position {
+                rot: 2,
+                row: 10,
+                col: 0
             }
-            path.push(start_move.pos);
-            Path::from_path_reverse(path, piece)
-        })
-        .collect()
+        );
+        // the NES rotation system does not kick
+        let nes = Move {
+            piece: Piece::from_name('I').unwrap(),
+            pos: Position {
+                rot: 1,
+                row: 10,
+                col: -2,
+            },
+        };
+        assert!(nes.is_valid(&board));
+        assert!(nes.rotate(&board, 0).is_none());
+    }
 }
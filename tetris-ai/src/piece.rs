@@ -1,56 +1,112 @@
 use crate::{
-    board::{BOARD_WIDTH, Cell},
+    board::{BoardSize, Cell},
     r#move::{Move, Position},
+    rotation::{Nes, RotationSystem},
 };
-use serde::Serialize;
+use serde::{Serialize, Serializer};
+use std::any::Any;
 #[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
 /// A rotatable tetromino piece.
+/// The shapes of the piece and how it rotates are defined by its rotation system.
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
-#[serde(transparent)]
-pub struct Piece(usize);
+#[derive(Clone, Copy)]
+pub struct Piece {
+    index: usize,
+    patterns: &'static [Pattern],
+    system: &'static dyn RotationSystem,
+}
+
+/// Pieces are equal if they have the same index and the same type of rotation system.
+/// The rotation systems are zero-sized, so their addresses cannot tell them apart.
+impl PartialEq for Piece {
+    fn eq(&self, other: &Self) -> bool {
+        let system = self.system as &dyn Any;
+        self.index == other.index && system.type_id() == (other.system as &dyn Any).type_id()
+    }
+}
+
+impl Eq for Piece {}
+
+impl std::fmt::Debug for Piece {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Piece")
+            .field("name", &PIECE_NAMES[self.index])
+            .field("system", &self.system)
+            .finish()
+    }
+}
+
+/// Pieces are serialized as their index, which is the same in every rotation system.
+impl Serialize for Piece {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.index.serialize(serializer)
+    }
+}
 
 impl Piece {
+    /// Returns the piece with the given index, using the NES rotation system.
     pub fn from_index(index: usize) -> Self {
         assert!(index < N_PIECES);
-        Piece(index)
+        Piece {
+            index,
+            patterns: Nes.patterns(index),
+            system: &Nes,
+        }
+    }
+
+    /// Returns the piece with the given name, e.g. 'T'.
+    pub fn from_name(name: char) -> Option<Self> {
+        PIECE_NAMES
+            .iter()
+            .position(|&n| n == name.to_ascii_uppercase())
+            .map(Piece::from_index)
+    }
+
+    /// Returns the same piece using the given rotation system.
+    pub fn with_rotation_system(self, system: &'static dyn RotationSystem) -> Self {
+        Piece {
+            index: self.index,
+            patterns: system.patterns(self.index),
+            system,
+        }
+    }
+
+    pub fn rotation_system(&self) -> &'static dyn RotationSystem {
+        self.system
     }
 
     #[inline]
     pub fn index(&self) -> usize {
-        self.0
+        self.index
     }
 
     #[inline]
     pub fn rotation(&self, rotation: usize) -> Pattern {
-        debug_assert!(rotation < self.num_rotations());
-        Pattern(PIECE_DATA[self.0].patterns[rotation])
+        self.patterns[rotation]
     }
 
     #[inline]
     pub fn num_rotations(&self) -> usize {
-        PIECE_DATA[self.0].patterns.len()
+        self.patterns.len()
     }
 
     #[inline]
     pub fn cell(&self) -> crate::board::Cell {
-        Cell::new(self.0 as u8 + 1)
+        Cell::new(self.index as u8 + 1)
     }
 
-    pub fn spawn_offset(&self) -> (isize, isize) {
-        PIECE_DATA[self.0].spawn_offset
-    }
-
-    pub fn into_start_move(self) -> Move {
-        let offset = self.spawn_offset();
+    /// Returns the move where the piece spawns, centered at the top of the board.
+    /// On boards with hidden rows, the piece spawns in the two rows above the visible rows.
+    pub fn into_start_move(self, size: BoardSize) -> Move {
+        let offset = self.system.spawn_offset(self.index);
         Move {
             piece: self,
             pos: Position {
                 rot: 0,
-                row: 0 - offset.0,
-                col: (BOARD_WIDTH / 2) as isize - offset.1,
+                row: size.hidden.saturating_sub(2) as isize - offset.0,
+                col: (size.width / 2) as isize - offset.1,
             },
         }
     }
@@ -60,172 +116,55 @@ impl Piece {
 impl Piece {
     #[cfg(feature = "wasm")]
     pub fn get_index(&self) -> usize {
-        self.0
+        self.index
     }
 }
 
 impl std::fmt::Display for Piece {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Piece {}", PIECE_DATA[self.0].name)
+        write!(f, "Piece {}", PIECE_NAMES[self.index])
     }
 }
 
-type Patterns<'a> = &'a [&'a [&'a [bool]]];
+/// The number of different pieces.
+pub const N_PIECES: usize = 7;
+
+/// The names of the pieces, ordered by their index.
+pub const PIECE_NAMES: [char; N_PIECES] = ['I', 'T', 'L', 'J', 'O', 'Z', 'S'];
 
-/// Data for each piece.
-pub struct PieceData {
-    name: char,
-    patterns: Patterns<'static>,
-    spawn_offset: (isize, isize),
+/// The maximum number of rotations of a piece.
+pub const MAX_ROTATIONS: usize = 4;
+
+/// The maximum number of rows and columns of a pattern.
+pub const MAX_PATTERN_SIZE: usize = 4;
+
+/// The square shape of a piece in a single rotation.
+/// Each row is stored as a bitmask, where bit `c` is set if column `c` is filled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pattern {
+    rows: [u16; MAX_PATTERN_SIZE],
+    size: usize,
 }
 
-// https://cdn.harddrop.com/0/07/NESTetris-pieces.png
-pub const N_PIECES: usize = 7;
+/// The smallest rectangle that contains all filled cells of a pattern.
+/// The rows and columns are offsets within the pattern, the ends are exclusive.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Bounds {
+    pub top: usize,
+    pub bottom: usize,
+    pub left: usize,
+    pub right: usize,
+}
+
+impl Bounds {
+    pub fn rows(&self) -> usize {
+        self.bottom - self.top
+    }
 
-#[rustfmt::skip]
-const PIECE_DATA: [PieceData; N_PIECES] = [
-    PieceData {
-        name: 'I',
-        patterns: &[
-            &[
-                &[false, false, false, false],
-                &[false, false, false, false],
-                &[true,  true,  true,  true],
-                &[false, false, false, false],
-            ],
-            &[
-                &[false, false, true, false],
-                &[false, false, true, false],
-                &[false, false, true, false],
-                &[false, false, true, false],
-            ],
-        ],
-        spawn_offset: (2, 2),
-    },
-    PieceData {
-        name: 'T',
-        patterns: &[
-            &[
-                &[false, false, false],
-                &[true, true, true],
-                &[false, true, false],
-            ],
-            &[
-                &[false, true, false],
-                &[true, true, false],
-                &[false, true, false],
-            ],
-            &[
-                &[false, true, false],
-                &[true, true, true],
-                &[false, false, false],
-            ],
-            &[
-                &[false, true, false],
-                &[false, true, true],
-                &[false, true, false],
-            ],
-        ],
-        spawn_offset: (1, 1),
-    },
-    PieceData {
-        name: 'L',
-        patterns: &[
-            &[
-                &[false, false, false],
-                &[true, true, true],
-                &[true, false, false],
-            ],
-            &[
-                &[true, true, false],
-                &[false, true, false],
-                &[false, true, false],
-            ],
-            &[
-                &[false, false, true],
-                &[true, true, true],
-                &[false, false, false],
-            ],
-            &[
-                &[false, true, false],
-                &[false, true, false],
-                &[false, true, true],
-            ],
-        ],
-        spawn_offset: (1, 1),
-    },
-    PieceData {
-        name: 'J',
-        patterns: &[
-            &[
-                &[false, false, false],
-                &[true, true, true],
-                &[false, false, true],
-            ],
-            &[
-                &[false, true, false],
-                &[false, true, false],
-                &[true, true, false],
-            ],
-            &[
-                &[true, false, false],
-                &[true, true, true],
-                &[false, false, false],
-            ],
-            &[
-                &[false, true, true],
-                &[false, true, false],
-                &[false, true, false],
-            ],
-        ],
-        spawn_offset: (1, 1),
-    },
-    PieceData {
-        name: 'O',
-        patterns: &[
-            &[
-                &[true, true],
-                &[true, true],
-            ],
-        ],
-        spawn_offset: (0, 1),
-    },
-    PieceData {
-        name: 'Z',
-        patterns: &[
-            &[
-                &[false, false, false],
-                &[true, true, false],
-                &[false, true, true],
-            ],
-            &[
-                &[false, true, false],
-                &[true, true, false],
-                &[true, false, false],
-            ],
-        ],
-        spawn_offset: (1, 1),
-    },
-    PieceData {
-        name: 'S',
-        patterns: &[
-            &[
-                &[false, false, false],
-                &[false, true, true],
-                &[true, true, false],
-            ],
-            &[
-                &[true, false, false],
-                &[true, true, false],
-                &[false, true, false],
-            ],
-        ],
-        spawn_offset: (1, 1),
-    },
-];
-
-#[derive(Clone, Debug, PartialEq, Eq)]
-pub struct Pattern(&'static [&'static [bool]]);
+    pub fn cols(&self) -> usize {
+        self.right - self.left
+    }
+}
 
 #[cfg_attr(feature = "wasm", wasm_bindgen(getter_with_clone))]
 pub struct WasmPattern {
@@ -234,28 +173,80 @@ pub struct WasmPattern {
 }
 
 impl Pattern {
-    pub fn iter_rows(&self) -> impl Iterator<Item = &[bool]> {
-        self.0.iter().copied()
+    pub(crate) const EMPTY: Pattern = Pattern {
+        rows: [0; MAX_PATTERN_SIZE],
+        size: 0,
+    };
+
+    pub(crate) const fn from_cells(cells: &[&[bool]]) -> Self {
+        assert!(cells.len() <= MAX_PATTERN_SIZE);
+        let mut rows = [0; MAX_PATTERN_SIZE];
+        let mut r = 0;
+        while r < cells.len() {
+            assert!(cells[r].len() == cells.len(), "pattern must be square");
+            let mut c = 0;
+            while c < cells[r].len() {
+                if cells[r][c] {
+                    rows[r] |= 1 << c;
+                }
+                c += 1;
+            }
+            r += 1;
+        }
+        Pattern {
+            rows,
+            size: cells.len(),
+        }
+    }
+
+    /// Returns the bitmasks of the rows.
+    pub fn iter_rows(&self) -> impl Iterator<Item = u16> + '_ {
+        self.rows[..self.size].iter().copied()
+    }
+
+    /// Returns the bitmask of a row, or 0 if the row is outside of the pattern.
+    #[inline]
+    pub fn row(&self, row: usize) -> u16 {
+        self.rows.get(row).copied().unwrap_or(0)
     }
 
-    pub fn get_row(&self, row: usize) -> Option<&[bool]> {
-        self.0.get(row).map(|v| &**v)
+    /// Returns true if the cell at the given row and column is filled.
+    #[inline]
+    pub fn filled(&self, row: usize, col: usize) -> bool {
+        self.row(row) & (1 << col) != 0
     }
 
     pub fn rows(&self) -> usize {
-        self.0.len()
+        self.size
     }
 
     pub fn cols(&self) -> usize {
-        self.0[0].len()
+        self.size
+    }
+
+    /// Returns the bounds of the filled cells, which excludes the empty rows and columns
+    /// that [`Pattern::rows`] and [`Pattern::cols`] include.
+    pub fn bounds(&self) -> Bounds {
+        let filled = &self.rows[..self.size];
+        let Some(top) = filled.iter().position(|&row| row != 0) else {
+            return Bounds::default();
+        };
+        let bottom = filled.iter().rposition(|&row| row != 0).unwrap() + 1;
+        let cols = filled.iter().fold(0, |acc, row| acc | row);
+        Bounds {
+            top,
+            bottom,
+            left: cols.trailing_zeros() as usize,
+            right: (u16::BITS - cols.leading_zeros()) as usize,
+        }
     }
 
     #[cfg(feature = "wasm")]
     pub fn into_wasm(self) -> WasmPattern {
         let mut data = Vec::with_capacity(self.rows() * self.cols());
-        for row in self.iter_rows() {
-            for cell in row {
-                data.push(if *cell { 1 } else { 0 });
+        for r in 0..self.rows() {
+            for c in 0..self.cols() {
+                data.push(if self.filled(r, c) { 1 } else { 0 });
             }
         }
 
@@ -269,12 +260,72 @@ impl Pattern {
 impl std::fmt::Display for Pattern {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f)?;
-        for row in self.iter_rows() {
-            for cell in row {
-                write!(f, "{}", if *cell { 'x' } else { '.' })?;
+        for r in 0..self.rows() {
+            for c in 0..self.cols() {
+                write!(f, "{}", if self.filled(r, c) { 'x' } else { '.' })?;
             }
             writeln!(f)?;
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rotation::{Srs, SrsNoKicks};
+
+    /// A rotation system that uses the name of another one.
+    struct Impostor;
+
+    impl RotationSystem for Impostor {
+        fn name(&self) -> &'static str {
+            "srs"
+        }
+
+        fn patterns(&self, piece: usize) -> &'static [Pattern] {
+            Srs.patterns(piece)
+        }
+
+        fn spawn_offset(&self, piece: usize) -> (isize, isize) {
+            Srs.spawn_offset(piece)
+        }
+    }
+
+    #[test]
+    fn test_piece_eq() {
+        let t = Piece::from_name('T').unwrap();
+        assert_eq!(t, Piece::from_index(t.index()));
+        assert_ne!(t, Piece::from_name('S').unwrap());
+        let srs = t.with_rotation_system(&Srs);
+        assert_ne!(t, srs);
+        assert_eq!(
+            srs,
+            Piece::from_name('T').unwrap().with_rotation_system(&Srs)
+        );
+        // the same shapes, but different kicks
+        assert_ne!(srs, t.with_rotation_system(&SrsNoKicks));
+        assert_ne!(srs, t.with_rotation_system(&Impostor));
+    }
+
+    #[test]
+    fn test_bounds() {
+        let pattern = Pattern::from_cells(&[
+            &[false, false, false],
+            &[true, true, true],
+            &[false, true, false],
+        ]);
+        let bounds = pattern.bounds();
+        assert_eq!(
+            bounds,
+            Bounds {
+                top: 1,
+                bottom: 3,
+                left: 0,
+                right: 3
+            }
+        );
+        assert_eq!((bounds.rows(), bounds.cols()), (2, 3));
+        assert_eq!(Pattern::EMPTY.bounds().rows(), 0);
+    }
+}
@@ -0,0 +1,333 @@
+use crate::{
+    feature::{FeatureContribution, Weights},
+    r#move::{Move, MoveGenerator, Path, Position, move_dijkstra},
+    piece::{N_PIECES, Piece},
+    rng::GameRng,
+    search::{Node, SearchBuffers, SearchMode, SearchStats, Searcher, keep_best},
+    state::State,
+    timing::Timing,
+};
+use rand::Rng;
+use serde::Serialize;
+
+/// Everything a policy knows when it decides where to place a piece.
+pub struct Decision<'a> {
+    /// The state before the piece is placed.
+    pub state: &'a State,
+    /// The piece to place.
+    pub piece: Piece,
+    /// The upcoming pieces, empty if there is no preview.
+    pub queue: &'a [Piece],
+    /// The held piece, which can be placed instead by swapping it with the current piece.
+    /// This is `None` if holding is not allowed.
+    pub hold: Option<Piece>,
+    /// The chance of each piece coming after the previewed pieces, indexed by piece index.
+    pub probabilities: [f64; N_PIECES],
+    /// Whether the number of moves is limited as the level increases.
+    pub time_pressure: bool,
+    /// Used for random choices, so that the game stays reproducible.
+    pub rng: &'a mut GameRng,
+}
+
+impl Decision<'_> {
+    /// Returns the pieces that can be placed, the current piece followed by the held piece.
+    /// Both are placed on the same board, so swapping them does not need a copy of the state.
+    pub fn options(&self) -> impl Iterator<Item = Piece> + use<> {
+        let held = self.hold.filter(|&held| held != self.piece);
+        std::iter::once(self.piece).chain(held)
+    }
+
+    /// Returns the paths to all the landing positions of the pieces that can be placed.
+    pub fn placements(&self) -> Vec<Path> {
+        self.options()
+            .flat_map(|piece| {
+                move_dijkstra(
+                    self.state.board(),
+                    piece,
+                    self.time_pressure.then(|| self.state.stats().level),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Decides where to place pieces.
+pub trait Policy {
+    /// Chooses the path of the piece to place, or `None` if no piece can be placed.
+    /// The path can place the held piece instead of the current piece.
+    fn choose(&mut self, decision: Decision<'_>) -> Option<Path>;
+
+    /// Returns statistics about the search performed in the last decision.
+    fn search_stats(&self) -> SearchStats {
+        SearchStats::default()
+    }
+
+    /// Returns why the last placement was chosen, if the policy can explain it.
+    fn explanation(&self) -> Option<&Explanation> {
+        None
+    }
+}
+
+/// A placement that was considered in a decision, see [`Explanation`].
+#[derive(Debug, Clone, Serialize)]
+pub struct Candidate {
+    pub r#move: Move,
+    /// The features of the state after the placement.
+    pub features: Vec<FeatureContribution>,
+    /// The evaluation of the state after the placement, which is the sum of the contributions.
+    pub evaluation: f64,
+    /// The score the placement got from the search, which includes searching ahead.
+    /// `None` if the placement was not searched further.
+    pub score: Option<f64>,
+}
+
+/// Why a placement was chosen, listing every placement of the current piece
+/// and of the held piece that was considered.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Explanation {
+    pub candidates: Vec<Candidate>,
+    /// The index of the chosen placement in the candidates, `None` if no piece could be placed.
+    pub chosen: Option<usize>,
+    /// The rank of the evaluation of the chosen placement, starting at 1.
+    /// Placements with the same evaluation share a rank, a rank above 1 means that searching ahead
+    /// chose a different placement than the greedy choice.
+    pub rank: usize,
+}
+
+/// Where a candidate placement comes from.
+#[derive(Debug, Clone, Copy)]
+struct Origin {
+    /// The index of the piece in [`Decision::options`].
+    option: usize,
+    pos: Position,
+    /// The index of the placement in the candidates of the [`Explanation`].
+    index: usize,
+}
+
+/// Any closure can be used as a policy, e.g. to drive the simulator externally or from a script.
+impl<F> Policy for F
+where
+    F: FnMut(Decision<'_>) -> Option<Path>,
+{
+    fn choose(&mut self, decision: Decision<'_>) -> Option<Path> {
+        self(decision)
+    }
+}
+
+/// Evaluates the landing positions with a linear combination of weighted features.
+#[derive(Debug, Clone, Default)]
+pub struct LinearPolicy {
+    weights: Weights,
+    search: SearchMode,
+    search_stats: SearchStats,
+    /// Whether to explain each decision, which evaluates the features of every candidate once more.
+    explain: bool,
+    explanation: Option<Explanation>,
+    /// Whether to evaluate placements incrementally, see [`LinearPolicy::set_incremental`].
+    incremental: bool,
+    /// One generator per option, so that the path to the chosen placement can be materialized.
+    root_generators: [MoveGenerator; 2],
+    search_generator: MoveGenerator,
+    /// The placements of the last decision, kept so that deciding does not allocate.
+    candidates: Vec<(Node, Origin, f64)>,
+    buffers: SearchBuffers,
+}
+
+impl LinearPolicy {
+    pub fn new(weights: Weights, search: SearchMode) -> Self {
+        Self {
+            weights,
+            search,
+            ..Default::default()
+        }
+    }
+
+    pub fn set_weights(&mut self, weights: Weights) {
+        self.weights = weights;
+    }
+
+    /// Sets how the policy searches for the best move.
+    /// Searching ahead uses the preview queue.
+    pub fn set_search(&mut self, search: SearchMode) {
+        self.search = search;
+    }
+
+    /// Enables or disables explaining each decision, see [`Policy::explanation`].
+    pub fn set_explain(&mut self, explain: bool) {
+        self.explain = explain;
+        self.explanation = None;
+    }
+
+    /// Evaluates the features that support it from the cache of the parent board, which is kept next to
+    /// the states in the search tree, see [`crate::cache::BoardCache`]. This gives the same evaluations,
+    /// but only pays off for expensive features, as the built-in features are cheap to compute from scratch.
+    pub fn set_incremental(&mut self, incremental: bool) {
+        self.incremental = incremental;
+    }
+
+    /// Sets how the time it takes to move a piece is modelled when generating moves.
+    pub fn set_timing(&mut self, timing: Timing) {
+        for generator in self.root_generators.iter_mut() {
+            generator.set_timing(timing);
+        }
+        self.search_generator.set_timing(timing);
+    }
+
+    /// Enables or disables rotating pieces by 180 degrees when generating moves.
+    pub fn set_rotate_180(&mut self, enabled: bool) {
+        for generator in self.root_generators.iter_mut() {
+            generator.set_rotate_180(enabled);
+        }
+        self.search_generator.set_rotate_180(enabled);
+    }
+}
+
+impl Policy for LinearPolicy {
+    fn choose(&mut self, decision: Decision<'_>) -> Option<Path> {
+        #[cfg(not(target_arch = "wasm32"))]
+        let start = std::time::Instant::now();
+        let mut searcher = Searcher::new(
+            &self.weights,
+            &mut self.search_generator,
+            &mut self.buffers,
+            decision.time_pressure,
+        )
+        .with_incremental(self.incremental);
+        let mut candidates = std::mem::take(&mut self.candidates);
+        candidates.clear();
+        // the futures of the held piece keep the held piece in the hold slot, which is not evaluated
+        for ((option, piece), generator) in decision
+            .options()
+            .enumerate()
+            .zip(self.root_generators.iter_mut())
+        {
+            let mut expansions = searcher.expand_into(generator, decision.state, piece);
+            let start = candidates.len();
+            candidates.extend(
+                expansions
+                    .drain(..)
+                    .enumerate()
+                    .map(|(i, (pos, future, score))| {
+                        let origin = Origin {
+                            option,
+                            pos,
+                            index: start + i,
+                        };
+                        (future, origin, score)
+                    }),
+            );
+            searcher.recycle(expansions);
+        }
+        let mut explanation = self.explain.then(|| Explanation {
+            candidates: candidates
+                .iter()
+                .map(|(future, _, score)| Candidate {
+                    r#move: future.state.delta().unwrap().r#move,
+                    features: self.weights.breakdown(&future.state),
+                    evaluation: *score,
+                    score: None,
+                })
+                .collect(),
+            ..Default::default()
+        });
+
+        let queue = decision.queue;
+        match self.search {
+            SearchMode::Greedy => {}
+            SearchMode::Lookahead { depth, width } => {
+                // Only the most promising placements are searched further
+                keep_best(&mut candidates, width);
+                let pieces = &queue[..depth.min(queue.len())];
+                for (future, _, score) in candidates.iter_mut() {
+                    *score = searcher.lookahead(future, pieces, width);
+                }
+            }
+            SearchMode::Expectimax { width } => {
+                keep_best(&mut candidates, width);
+                // A previewed piece is certain to come next
+                let probabilities = match queue.first() {
+                    Some(next) => {
+                        let mut probabilities = [0.0; N_PIECES];
+                        probabilities[next.index()] = 1.0;
+                        probabilities
+                    }
+                    None => decision.probabilities,
+                };
+                for (future, _, score) in candidates.iter_mut() {
+                    *score = searcher.expectimax(future, &probabilities);
+                }
+            }
+            SearchMode::Beam { depth, width } => {
+                keep_best(&mut candidates, width);
+                searcher.beam(&mut candidates, &queue[..depth.min(queue.len())], width);
+            }
+        }
+        self.search_stats = SearchStats {
+            nodes: searcher.nodes(),
+            #[cfg(not(target_arch = "wasm32"))]
+            micros: start.elapsed().as_micros() as u64,
+            #[cfg(target_arch = "wasm32")]
+            micros: 0,
+        };
+
+        if let Some(explanation) = explanation.as_mut() {
+            for (_, origin, score) in candidates.iter() {
+                explanation.candidates[origin.index].score = Some(*score);
+            }
+        }
+
+        // Use resivoir sampling to ramdomly select one of the best possible moves
+        let mut chosen = None;
+        let mut best_score = f64::NEG_INFINITY;
+        let mut count = 0;
+        for &(_, destination, score) in candidates.iter() {
+            if chosen.is_none() || score > best_score {
+                best_score = score;
+                chosen = Some(destination);
+                count = 1;
+            } else if score == best_score {
+                count += 1;
+                if decision.rng.random_range(0..count) == 0 {
+                    chosen = Some(destination);
+                }
+            }
+        }
+        self.candidates = candidates;
+        if let Some(mut explanation) = explanation {
+            if let Some(origin) = chosen {
+                let evaluation = explanation.candidates[origin.index].evaluation;
+                explanation.chosen = Some(origin.index);
+                explanation.rank = 1 + explanation
+                    .candidates
+                    .iter()
+                    .filter(|candidate| candidate.evaluation > evaluation)
+                    .count();
+            }
+            self.explanation = Some(explanation);
+        }
+        chosen.map(|origin| self.root_generators[origin.option].path(origin.pos))
+    }
+
+    fn search_stats(&self) -> SearchStats {
+        self.search_stats
+    }
+
+    fn explanation(&self) -> Option<&Explanation> {
+        self.explanation.as_ref()
+    }
+}
+
+/// Places pieces at random landing positions, useful as a baseline.
+#[derive(Debug, Clone, Default)]
+pub struct RandomPolicy;
+
+impl Policy for RandomPolicy {
+    fn choose(&mut self, decision: Decision<'_>) -> Option<Path> {
+        let mut placements = decision.placements();
+        if placements.is_empty() {
+            return None;
+        }
+        let index = decision.rng.random_range(0..placements.len());
+        Some(placements.swap_remove(index))
+    }
+}
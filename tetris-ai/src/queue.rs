@@ -0,0 +1,100 @@
+use crate::{
+    piece::Piece,
+    rng::{GameRng, Randomizer},
+    rotation::{Nes, RotationSystem},
+};
+
+/// The maximum number of upcoming pieces that can be previewed.
+pub const MAX_PREVIEW: usize = 6;
+
+/// The queue of upcoming pieces that can be previewed.
+/// The pieces are dealt in the rotation system of the queue.
+#[derive(Debug, Clone, Copy)]
+pub struct Queue {
+    pieces: [Piece; MAX_PREVIEW],
+    len: usize,
+    size: usize,
+    system: &'static dyn RotationSystem,
+}
+
+impl Default for Queue {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl Queue {
+    /// Creates an empty queue that previews `size` pieces.
+    pub fn new(size: usize) -> Self {
+        assert!(
+            size <= MAX_PREVIEW,
+            "preview size must be at most {MAX_PREVIEW}"
+        );
+        Self {
+            pieces: [Piece::from_index(0); MAX_PREVIEW],
+            len: 0,
+            size,
+            system: &Nes,
+        }
+    }
+
+    /// Sets the rotation system of the pieces that are dealt.
+    pub fn with_rotation_system(mut self, system: &'static dyn RotationSystem) -> Self {
+        self.system = system;
+        self
+    }
+
+    pub fn rotation_system(&self) -> &'static dyn RotationSystem {
+        self.system
+    }
+
+    /// Takes the next piece from the queue and refills it using the randomizer.
+    pub fn pop(&mut self, randomizer: &mut dyn Randomizer, rng: &mut GameRng) -> Piece {
+        let piece = if self.len == 0 {
+            randomizer.next_piece(rng).with_rotation_system(self.system)
+        } else {
+            let piece = self.pieces[0];
+            self.pieces.copy_within(1..self.len, 0);
+            self.len -= 1;
+            piece
+        };
+        while self.len < self.size {
+            self.pieces[self.len] = randomizer.next_piece(rng).with_rotation_system(self.system);
+            self.len += 1;
+        }
+        piece
+    }
+
+    /// Returns the upcoming pieces, the first piece comes next.
+    pub fn pieces(&self) -> &[Piece] {
+        &self.pieces[..self.len]
+    }
+
+    /// Returns the number of pieces that are previewed.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::{RandomizerKind, seeded_rng};
+
+    #[test]
+    fn test_queue_keeps_sequence() {
+        let kind = "fixed:ITLJOZS".parse::<RandomizerKind>().unwrap();
+        let mut rng = seeded_rng(Some(0));
+        for size in 0..=MAX_PREVIEW {
+            let mut randomizer = kind.build();
+            let mut queue = Queue::new(size);
+            for i in 0..20 {
+                assert_eq!(queue.pop(&mut *randomizer, &mut rng).index(), i % 7);
+                assert_eq!(queue.pieces().len(), size);
+                for (j, piece) in queue.pieces().iter().enumerate() {
+                    assert_eq!(piece.index(), (i + j + 1) % 7);
+                }
+            }
+        }
+    }
+}
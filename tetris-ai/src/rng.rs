@@ -1,13 +1,367 @@
 use crate::piece::{N_PIECES, Piece};
-use rand::Rng;
+use rand::{Rng, SeedableRng, seq::SliceRandom};
+use rand_chacha::ChaCha12Rng;
+use std::str::FromStr;
+
+/// The random number generator used by the engine.
+/// It is named explicitly instead of using `StdRng`, which may change between versions of `rand`,
+/// so that a seed reproduces the same sequence on every platform, including wasm.
+pub type GameRng = ChaCha12Rng;
+
+/// Creates the random number generator used by the engine.
+/// Without a seed, the generator is seeded from the operating system.
+pub fn seeded_rng(seed: Option<u64>) -> GameRng {
+    match seed {
+        Some(seed) => GameRng::seed_from_u64(seed),
+        None => GameRng::from_os_rng(),
+    }
+}
 
 // Based on how NES Tetris generates pieces
-pub fn gen_random_piece(previous: Option<usize>) -> Piece {
-    let first_roll = rand::rng().random_range(0..=N_PIECES);
+pub fn gen_random_piece(rng: &mut GameRng, previous: Option<usize>) -> Piece {
+    let first_roll = rng.random_range(0..=N_PIECES);
     if first_roll == N_PIECES || previous == Some(first_roll) {
         // reroll if the first roll is the same as the previous piece
         // or if the 'reroll' number is hit
-        return Piece::from_index(rand::rng().random_range(0..N_PIECES));
+        return Piece::from_index(rng.random_range(0..N_PIECES));
     }
     Piece::from_index(first_roll)
 }
+
+/// Generates the sequence of pieces in a game.
+pub trait Randomizer {
+    /// Returns the next piece in the sequence.
+    fn next_piece(&mut self, rng: &mut GameRng) -> Piece;
+
+    /// Forgets all previously generated pieces, used when a new game starts.
+    fn reset(&mut self);
+
+    /// Returns the probability of each piece being generated next, indexed by piece index.
+    /// Defaults to a uniform distribution.
+    fn probabilities(&self) -> [f64; N_PIECES] {
+        [1.0 / N_PIECES as f64; N_PIECES]
+    }
+}
+
+/// The NES algorithm, which rerolls once if the same piece comes up twice.
+#[derive(Debug, Default, Clone)]
+pub struct Nes {
+    previous: Option<usize>,
+}
+
+impl Randomizer for Nes {
+    fn next_piece(&mut self, rng: &mut GameRng) -> Piece {
+        let piece = gen_random_piece(rng, self.previous);
+        self.previous = Some(piece.index());
+        piece
+    }
+
+    fn reset(&mut self) {
+        self.previous = None;
+    }
+
+    fn probabilities(&self) -> [f64; N_PIECES] {
+        let roll = 1.0 / (N_PIECES + 1) as f64;
+        // the reroll number and the previous piece lead to a uniform reroll
+        let rerolls = if self.previous.is_some() { 2.0 } else { 1.0 };
+        let mut probabilities = [roll + rerolls * roll / N_PIECES as f64; N_PIECES];
+        if let Some(previous) = self.previous {
+            probabilities[previous] -= roll;
+        }
+        probabilities
+    }
+}
+
+/// Every piece is equally likely, independent of the previous pieces.
+#[derive(Debug, Default, Clone)]
+pub struct Uniform;
+
+impl Randomizer for Uniform {
+    fn next_piece(&mut self, rng: &mut GameRng) -> Piece {
+        Piece::from_index(rng.random_range(0..N_PIECES))
+    }
+
+    fn reset(&mut self) {}
+}
+
+/// Deals pieces from a shuffled bag containing `copies` of every piece,
+/// the modern 7-bag uses a single copy and the 14-bag two copies.
+#[derive(Debug, Clone)]
+pub struct Bag {
+    copies: usize,
+    bag: Vec<Piece>,
+}
+
+impl Bag {
+    pub fn new(copies: usize) -> Self {
+        assert!(copies > 0);
+        Self {
+            copies,
+            bag: Vec::with_capacity(copies * N_PIECES),
+        }
+    }
+}
+
+impl Randomizer for Bag {
+    fn next_piece(&mut self, rng: &mut GameRng) -> Piece {
+        if self.bag.is_empty() {
+            for index in 0..N_PIECES {
+                for _ in 0..self.copies {
+                    self.bag.push(Piece::from_index(index));
+                }
+            }
+            self.bag.shuffle(rng);
+        }
+        self.bag.pop().unwrap()
+    }
+
+    fn reset(&mut self) {
+        self.bag.clear();
+    }
+
+    fn probabilities(&self) -> [f64; N_PIECES] {
+        if self.bag.is_empty() {
+            return [1.0 / N_PIECES as f64; N_PIECES];
+        }
+        let mut probabilities = [0.0; N_PIECES];
+        for piece in self.bag.iter() {
+            probabilities[piece.index()] += 1.0 / self.bag.len() as f64;
+        }
+        probabilities
+    }
+}
+
+const TGM_ROLLS: usize = 4;
+// I, T, L and J, the first piece is never an S, Z or O
+const TGM_FIRST_PIECES: [usize; 4] = [0, 1, 2, 3];
+// starts out filled with Z pieces
+const TGM_HISTORY: [usize; 4] = [5; 4];
+
+/// The TGM algorithm, which rolls up to four times to find a piece that
+/// is not in the history of the last four pieces.
+#[derive(Debug, Clone)]
+pub struct Tgm {
+    history: [usize; 4],
+    first: bool,
+}
+
+impl Default for Tgm {
+    fn default() -> Self {
+        Self {
+            history: TGM_HISTORY,
+            first: true,
+        }
+    }
+}
+
+impl Randomizer for Tgm {
+    fn next_piece(&mut self, rng: &mut GameRng) -> Piece {
+        let index = if self.first {
+            self.first = false;
+            TGM_FIRST_PIECES[rng.random_range(0..TGM_FIRST_PIECES.len())]
+        } else {
+            let mut index = rng.random_range(0..N_PIECES);
+            for _ in 1..TGM_ROLLS {
+                if !self.history.contains(&index) {
+                    break;
+                }
+                index = rng.random_range(0..N_PIECES);
+            }
+            index
+        };
+        self.history.rotate_right(1);
+        self.history[0] = index;
+        Piece::from_index(index)
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    fn probabilities(&self) -> [f64; N_PIECES] {
+        let mut probabilities = [0.0; N_PIECES];
+        if self.first {
+            for index in TGM_FIRST_PIECES {
+                probabilities[index] = 1.0 / TGM_FIRST_PIECES.len() as f64;
+            }
+            return probabilities;
+        }
+        // the chance that a single roll hits a piece in the history
+        let in_history = (0..N_PIECES)
+            .filter(|index| self.history.contains(index))
+            .count() as f64
+            / N_PIECES as f64;
+        // the last roll is always accepted, earlier rolls only if not in the history
+        let last_roll = in_history.powi(TGM_ROLLS as i32 - 1) / N_PIECES as f64;
+        let earlier_rolls = (0..TGM_ROLLS - 1)
+            .map(|i| in_history.powi(i as i32) / N_PIECES as f64)
+            .sum::<f64>();
+        for (index, probability) in probabilities.iter_mut().enumerate() {
+            *probability = if self.history.contains(&index) {
+                last_roll
+            } else {
+                earlier_rolls + last_roll
+            };
+        }
+        probabilities
+    }
+}
+
+/// Repeats a scripted sequence of pieces, useful for tests and puzzles.
+#[derive(Debug, Clone)]
+pub struct Fixed {
+    sequence: Vec<Piece>,
+    index: usize,
+}
+
+impl Fixed {
+    pub fn new(sequence: Vec<Piece>) -> Self {
+        assert!(!sequence.is_empty(), "sequence must not be empty");
+        Self { sequence, index: 0 }
+    }
+}
+
+impl Randomizer for Fixed {
+    fn next_piece(&mut self, _rng: &mut GameRng) -> Piece {
+        let piece = self.sequence[self.index];
+        self.index = (self.index + 1) % self.sequence.len();
+        piece
+    }
+
+    fn reset(&mut self) {
+        self.index = 0;
+    }
+
+    fn probabilities(&self) -> [f64; N_PIECES] {
+        let mut probabilities = [0.0; N_PIECES];
+        probabilities[self.sequence[self.index].index()] = 1.0;
+        probabilities
+    }
+}
+
+/// Describes a randomizer, so that it can be selected by name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum RandomizerKind {
+    #[default]
+    Nes,
+    Bag7,
+    Bag14,
+    Uniform,
+    Tgm,
+    Fixed(Vec<Piece>),
+}
+
+impl RandomizerKind {
+    pub fn build(&self) -> Box<dyn Randomizer> {
+        match self {
+            RandomizerKind::Nes => Box::new(Nes::default()),
+            RandomizerKind::Bag7 => Box::new(Bag::new(1)),
+            RandomizerKind::Bag14 => Box::new(Bag::new(2)),
+            RandomizerKind::Uniform => Box::new(Uniform),
+            RandomizerKind::Tgm => Box::new(Tgm::default()),
+            RandomizerKind::Fixed(sequence) => Box::new(Fixed::new(sequence.clone())),
+        }
+    }
+}
+
+impl FromStr for RandomizerKind {
+    type Err = ();
+
+    /// Parses a randomizer name, a fixed sequence is written as e.g. `fixed:ITLJOZS`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nes" => Ok(RandomizerKind::Nes),
+            "bag7" => Ok(RandomizerKind::Bag7),
+            "bag14" => Ok(RandomizerKind::Bag14),
+            "uniform" => Ok(RandomizerKind::Uniform),
+            "tgm" => Ok(RandomizerKind::Tgm),
+            _ => {
+                let sequence = s
+                    .strip_prefix("fixed:")
+                    .ok_or(())?
+                    .chars()
+                    .map(Piece::from_name)
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or(())?;
+                if sequence.is_empty() {
+                    return Err(());
+                }
+                Ok(RandomizerKind::Fixed(sequence))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generate(kind: &str, count: usize) -> Vec<usize> {
+        let mut rng = seeded_rng(Some(0));
+        let mut randomizer = kind.parse::<RandomizerKind>().unwrap().build();
+        (0..count)
+            .map(|_| randomizer.next_piece(&mut rng).index())
+            .collect()
+    }
+
+    #[test]
+    fn test_bags_contain_all_pieces() {
+        for (kind, copies) in [("bag7", 1), ("bag14", 2)] {
+            let pieces = generate(kind, copies * N_PIECES * 10);
+            for bag in pieces.chunks(copies * N_PIECES) {
+                for index in 0..N_PIECES {
+                    assert_eq!(bag.iter().filter(|&&i| i == index).count(), copies);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_fixed_sequence() {
+        assert_eq!(generate("fixed:IOT", 7), vec![0, 4, 1, 0, 4, 1, 0]);
+        assert!("fixed:".parse::<RandomizerKind>().is_err());
+        assert!("fixed:IX".parse::<RandomizerKind>().is_err());
+    }
+
+    #[test]
+    fn test_probabilities() {
+        // compares the probabilities with the observed frequencies
+        const SAMPLES: usize = 100_000;
+        let mut rng = seeded_rng(Some(0));
+        for kind in ["nes", "bag7", "bag14", "uniform", "tgm", "fixed:ITTO"] {
+            let mut randomizer = kind.parse::<RandomizerKind>().unwrap().build();
+            let mut expected = [0.0; N_PIECES];
+            let mut observed = [0.0; N_PIECES];
+            for _ in 0..SAMPLES {
+                let probabilities = randomizer.probabilities();
+                assert!((probabilities.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+                for (e, p) in expected.iter_mut().zip(probabilities) {
+                    *e += p / SAMPLES as f64;
+                }
+                observed[randomizer.next_piece(&mut rng).index()] += 1.0 / SAMPLES as f64;
+            }
+            for (e, o) in expected.iter().zip(observed) {
+                assert!((e - o).abs() < 0.01, "{kind}: {expected:?} != {observed:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_tgm_first_piece() {
+        for seed in 0..100 {
+            let mut rng = seeded_rng(Some(seed));
+            let piece = Tgm::default().next_piece(&mut rng);
+            assert!(TGM_FIRST_PIECES.contains(&piece.index()));
+        }
+    }
+
+    #[test]
+    fn test_all_randomizers_generate_every_piece() {
+        for kind in ["nes", "bag7", "bag14", "uniform", "tgm"] {
+            let pieces = generate(kind, 1000);
+            for index in 0..N_PIECES {
+                assert!(pieces.contains(&index), "{kind} never generated {index}");
+            }
+        }
+    }
+}
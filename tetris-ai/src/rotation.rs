@@ -0,0 +1,847 @@
+use crate::{
+    board::Board,
+    r#move::{Move, Position},
+    piece::{MAX_ROTATIONS, N_PIECES, PIECE_NAMES, Pattern},
+};
+use std::{any::Any, str::FromStr};
+
+/// The maximum number of positions that are tried when rotating a piece.
+pub const MAX_KICKS: usize = 5;
+
+/// The offsets that are tried in order when rotating a piece, as (row, column) offsets.
+/// The first offset that does not overlap with the board is used.
+type Kicks = [(isize, isize); MAX_KICKS];
+
+/// Only tries to rotate in place.
+pub const NO_KICKS: [(isize, isize); 1] = [(0, 0)];
+
+/// Defines how pieces are shaped, where they spawn and how they are kicked when rotating.
+/// Pieces are identified by their index, see [`PIECE_NAMES`], and rotations are numbered clockwise.
+/// Rotation systems are told apart by their type, see [`Piece`](crate::piece::Piece).
+pub trait RotationSystem: Any {
+    /// The name used to select the rotation system.
+    fn name(&self) -> &'static str;
+
+    /// Returns the patterns of every distinct rotation of a piece, starting with the spawn rotation.
+    fn patterns(&self, piece: usize) -> &'static [Pattern];
+
+    /// Returns the (row, column) of the pattern cell that is placed at the spawn position.
+    fn spawn_offset(&self, piece: usize) -> (isize, isize);
+
+    /// Returns the offsets to try in order when rotating a piece from one rotation to another.
+    /// Besides quarter turns, this is also used for 180 degree rotations.
+    fn kicks(&self, _piece: usize, _from: usize, _to: usize) -> &'static [(isize, isize)] {
+        &NO_KICKS
+    }
+
+    /// Rotates a move to the given rotation, or returns `None` if the rotation is blocked.
+    /// By default, the kicks are tried in order.
+    fn rotate(&self, board: &Board, r#move: Move, rot: usize) -> Option<Move> {
+        kick(
+            board,
+            r#move,
+            rot,
+            self.kicks(r#move.piece.index(), r#move.pos.rot, rot),
+        )
+    }
+}
+
+impl std::fmt::Debug for dyn RotationSystem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Rotates a move, using the first of the offsets that does not overlap with the board.
+fn kick(board: &Board, r#move: Move, rot: usize, kicks: &[(isize, isize)]) -> Option<Move> {
+    kicks
+        .iter()
+        .map(|&(row, col)| Move {
+            piece: r#move.piece,
+            pos: Position {
+                rot,
+                row: r#move.pos.row + row,
+                col: r#move.pos.col + col,
+            },
+        })
+        .find(|r#move| r#move.is_valid(board))
+}
+
+/// Used to select one of the shipped rotation systems.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RotationSystemKind {
+    #[default]
+    Nes,
+    Srs,
+    Ars,
+    SrsNoKicks,
+}
+
+impl RotationSystemKind {
+    pub fn system(&self) -> &'static dyn RotationSystem {
+        match self {
+            RotationSystemKind::Nes => &Nes,
+            RotationSystemKind::Srs => &Srs,
+            RotationSystemKind::Ars => &Ars,
+            RotationSystemKind::SrsNoKicks => &SrsNoKicks,
+        }
+    }
+}
+
+impl FromStr for RotationSystemKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nes" => Ok(RotationSystemKind::Nes),
+            "srs" => Ok(RotationSystemKind::Srs),
+            "ars" => Ok(RotationSystemKind::Ars),
+            "srs-nokicks" => Ok(RotationSystemKind::SrsNoKicks),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The rotation system of NES Tetris, pieces only rotate in place.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Nes;
+
+impl RotationSystem for Nes {
+    fn name(&self) -> &'static str {
+        "nes"
+    }
+
+    fn patterns(&self, piece: usize) -> &'static [Pattern] {
+        NES_SHAPES.patterns(piece)
+    }
+
+    fn spawn_offset(&self, piece: usize) -> (isize, isize) {
+        NES_DATA[piece].spawn_offset
+    }
+}
+
+/// The Super Rotation System of modern guideline games, with wall kicks.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Srs;
+
+impl RotationSystem for Srs {
+    fn name(&self) -> &'static str {
+        "srs"
+    }
+
+    fn patterns(&self, piece: usize) -> &'static [Pattern] {
+        SRS_SHAPES.patterns(piece)
+    }
+
+    fn spawn_offset(&self, piece: usize) -> (isize, isize) {
+        SRS_DATA[piece].spawn_offset
+    }
+
+    fn kicks(&self, piece: usize, from: usize, to: usize) -> &'static [(isize, isize)] {
+        let table = match PIECE_NAMES[piece] {
+            'I' => &SRS_I_KICKS,
+            'O' => return &NO_KICKS,
+            _ => &SRS_JLSTZ_KICKS,
+        };
+        if (from + 1) % MAX_ROTATIONS == to {
+            &table[from]
+        } else if (from + 2) % MAX_ROTATIONS == to {
+            &NO_KICKS // the guideline does not define kicks for 180 degree rotations
+        } else {
+            debug_assert_eq!(from, (to + 1) % MAX_ROTATIONS);
+            &table[MAX_ROTATIONS + to]
+        }
+    }
+}
+
+/// The shapes and spawn orientations of the guideline games, but pieces only rotate in place.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SrsNoKicks;
+
+impl RotationSystem for SrsNoKicks {
+    fn name(&self) -> &'static str {
+        "srs-nokicks"
+    }
+
+    fn patterns(&self, piece: usize) -> &'static [Pattern] {
+        SRS_SHAPES.patterns(piece)
+    }
+
+    fn spawn_offset(&self, piece: usize) -> (isize, isize) {
+        SRS_DATA[piece].spawn_offset
+    }
+}
+
+/// The Arika Rotation System of the Tetris The Grand Master games (TGM1 and TGM2).
+/// Pieces rest on the bottom of their bounding box and kick one column to the right,
+/// then one column to the left. The I piece never kicks.
+// https://tetris.wiki/Arika_Rotation_System
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Ars;
+
+impl RotationSystem for Ars {
+    fn name(&self) -> &'static str {
+        "ars"
+    }
+
+    fn patterns(&self, piece: usize) -> &'static [Pattern] {
+        ARS_SHAPES.patterns(piece)
+    }
+
+    fn spawn_offset(&self, piece: usize) -> (isize, isize) {
+        ARS_DATA[piece].spawn_offset
+    }
+
+    fn kicks(&self, piece: usize, _from: usize, _to: usize) -> &'static [(isize, isize)] {
+        match PIECE_NAMES[piece] {
+            'I' | 'O' => &NO_KICKS,
+            _ => &[(0, 0), (0, 1), (0, -1)],
+        }
+    }
+
+    fn rotate(&self, board: &Board, r#move: Move, rot: usize) -> Option<Move> {
+        let piece = r#move.piece.index();
+        // The J, L and T pieces do not kick when the first blocked cell, in reading order,
+        // is in the center column of the bounding box.
+        if matches!(PIECE_NAMES[piece], 'J' | 'L' | 'T') {
+            let rotated = Move {
+                piece: r#move.piece,
+                pos: Position { rot, ..r#move.pos },
+            };
+            if rotated.is_valid(board) {
+                return Some(rotated);
+            }
+            if first_blocked_col(board, rotated) == Some(1) {
+                return None;
+            }
+        }
+        kick(board, r#move, rot, self.kicks(piece, r#move.pos.rot, rot))
+    }
+}
+
+/// Returns the column in the pattern of the first cell that overlaps with the board,
+/// scanning the rows from top to bottom and the columns from left to right.
+fn first_blocked_col(board: &Board, r#move: Move) -> Option<usize> {
+    let pattern = r#move.pattern();
+    let size = board.size();
+    for r in 0..pattern.rows() {
+        for c in 0..pattern.cols() {
+            if !pattern.filled(r, c) {
+                continue;
+            }
+            let row = r#move.pos.row + r as isize;
+            let col = r#move.pos.col + c as isize;
+            if row < 0 {
+                continue; // cells above the board are never blocked
+            }
+            if row >= size.height as isize
+                || col < 0
+                || col >= size.width as isize
+                || board[(row as usize, col as usize)].filled()
+            {
+                return Some(c);
+            }
+        }
+    }
+    None
+}
+
+/// The SRS kicks of the J, L, S, T and Z pieces.
+/// The first four rows rotate clockwise from rotation 0, R, 2 and L,
+/// the last four rows rotate counter-clockwise to rotation 0, R, 2 and L.
+/// Upwards kicks have a negative row offset, as rows are counted from the top.
+// https://tetris.wiki/Super_Rotation_System#Wall_Kicks
+#[rustfmt::skip]
+const SRS_JLSTZ_KICKS: [Kicks; 2 * MAX_ROTATIONS] = [
+    [(0, 0), (0, -1), (-1, -1), (2, 0), (2, -1)], // 0 -> R
+    [(0, 0), (0, 1), (1, 1), (-2, 0), (-2, 1)],   // R -> 2
+    [(0, 0), (0, 1), (-1, 1), (2, 0), (2, 1)],    // 2 -> L
+    [(0, 0), (0, -1), (1, -1), (-2, 0), (-2, -1)], // L -> 0
+    [(0, 0), (0, 1), (1, 1), (-2, 0), (-2, 1)],   // R -> 0
+    [(0, 0), (0, -1), (-1, -1), (2, 0), (2, -1)], // 2 -> R
+    [(0, 0), (0, -1), (1, -1), (-2, 0), (-2, -1)], // L -> 2
+    [(0, 0), (0, 1), (-1, 1), (2, 0), (2, 1)],    // 0 -> L
+];
+
+/// The SRS kicks of the I piece, in the same order as [`SRS_JLSTZ_KICKS`].
+#[rustfmt::skip]
+const SRS_I_KICKS: [Kicks; 2 * MAX_ROTATIONS] = [
+    [(0, 0), (0, -2), (0, 1), (1, -2), (-2, 1)],  // 0 -> R
+    [(0, 0), (0, -1), (0, 2), (-2, -1), (1, 2)],  // R -> 2
+    [(0, 0), (0, 2), (0, -1), (-1, 2), (2, -1)],  // 2 -> L
+    [(0, 0), (0, 1), (0, -2), (2, 1), (-1, -2)],  // L -> 0
+    [(0, 0), (0, 2), (0, -1), (-1, 2), (2, -1)],  // R -> 0
+    [(0, 0), (0, 1), (0, -2), (2, 1), (-1, -2)],  // 2 -> R
+    [(0, 0), (0, -2), (0, 1), (1, -2), (-2, 1)],  // L -> 2
+    [(0, 0), (0, -1), (0, 2), (-2, -1), (1, 2)],  // 0 -> L
+];
+
+/// The shape data of each piece, in the order of [`PIECE_NAMES`].
+struct PieceData {
+    patterns: &'static [&'static [&'static [bool]]],
+    spawn_offset: (isize, isize),
+}
+
+/// The bitmask patterns of every rotation of each piece, computed from the shape data.
+struct Shapes {
+    patterns: [[Pattern; MAX_ROTATIONS]; N_PIECES],
+    rotations: [usize; N_PIECES],
+}
+
+impl Shapes {
+    const fn new(data: &[PieceData; N_PIECES]) -> Self {
+        let mut patterns = [[Pattern::EMPTY; MAX_ROTATIONS]; N_PIECES];
+        let mut rotations = [0; N_PIECES];
+        let mut piece = 0;
+        while piece < N_PIECES {
+            let mut rotation = 0;
+            while rotation < data[piece].patterns.len() {
+                patterns[piece][rotation] = Pattern::from_cells(data[piece].patterns[rotation]);
+                rotation += 1;
+            }
+            rotations[piece] = rotation;
+            piece += 1;
+        }
+        Shapes {
+            patterns,
+            rotations,
+        }
+    }
+
+    fn patterns(&'static self, piece: usize) -> &'static [Pattern] {
+        &self.patterns[piece][..self.rotations[piece]]
+    }
+}
+
+static NES_SHAPES: Shapes = Shapes::new(&NES_DATA);
+static SRS_SHAPES: Shapes = Shapes::new(&SRS_DATA);
+static ARS_SHAPES: Shapes = Shapes::new(&ARS_DATA);
+
+// https://cdn.harddrop.com/0/07/NESTetris-pieces.png
+#[rustfmt::skip]
+const NES_DATA: [PieceData; N_PIECES] = [
+    PieceData {
+        patterns: &[
+            &[
+                &[false, false, false, false],
+                &[false, false, false, false],
+                &[true,  true,  true,  true],
+                &[false, false, false, false],
+            ],
+            &[
+                &[false, false, true, false],
+                &[false, false, true, false],
+                &[false, false, true, false],
+                &[false, false, true, false],
+            ],
+        ],
+        spawn_offset: (2, 2),
+    },
+    PieceData {
+        patterns: &[
+            &[
+                &[false, false, false],
+                &[true, true, true],
+                &[false, true, false],
+            ],
+            &[
+                &[false, true, false],
+                &[true, true, false],
+                &[false, true, false],
+            ],
+            &[
+                &[false, true, false],
+                &[true, true, true],
+                &[false, false, false],
+            ],
+            &[
+                &[false, true, false],
+                &[false, true, true],
+                &[false, true, false],
+            ],
+        ],
+        spawn_offset: (1, 1),
+    },
+    PieceData {
+        patterns: &[
+            &[
+                &[false, false, false],
+                &[true, true, true],
+                &[true, false, false],
+            ],
+            &[
+                &[true, true, false],
+                &[false, true, false],
+                &[false, true, false],
+            ],
+            &[
+                &[false, false, true],
+                &[true, true, true],
+                &[false, false, false],
+            ],
+            &[
+                &[false, true, false],
+                &[false, true, false],
+                &[false, true, true],
+            ],
+        ],
+        spawn_offset: (1, 1),
+    },
+    PieceData {
+        patterns: &[
+            &[
+                &[false, false, false],
+                &[true, true, true],
+                &[false, false, true],
+            ],
+            &[
+                &[false, true, false],
+                &[false, true, false],
+                &[true, true, false],
+            ],
+            &[
+                &[true, false, false],
+                &[true, true, true],
+                &[false, false, false],
+            ],
+            &[
+                &[false, true, true],
+                &[false, true, false],
+                &[false, true, false],
+            ],
+        ],
+        spawn_offset: (1, 1),
+    },
+    PieceData {
+        patterns: &[
+            &[
+                &[true, true],
+                &[true, true],
+            ],
+        ],
+        spawn_offset: (0, 1),
+    },
+    PieceData {
+        patterns: &[
+            &[
+                &[false, false, false],
+                &[true, true, false],
+                &[false, true, true],
+            ],
+            &[
+                &[false, true, false],
+                &[true, true, false],
+                &[true, false, false],
+            ],
+        ],
+        spawn_offset: (1, 1),
+    },
+    PieceData {
+        patterns: &[
+            &[
+                &[false, false, false],
+                &[false, true, true],
+                &[true, true, false],
+            ],
+            &[
+                &[true, false, false],
+                &[true, true, false],
+                &[false, true, false],
+            ],
+        ],
+        spawn_offset: (1, 1),
+    },
+];
+
+// https://tetris.wiki/Super_Rotation_System
+// The pieces are in the same order as the NES pieces.
+#[rustfmt::skip]
+const SRS_DATA: [PieceData; N_PIECES] = [
+    PieceData {
+        patterns: &[
+            &[
+                &[false, false, false, false],
+                &[true,  true,  true,  true],
+                &[false, false, false, false],
+                &[false, false, false, false],
+            ],
+            &[
+                &[false, false, true, false],
+                &[false, false, true, false],
+                &[false, false, true, false],
+                &[false, false, true, false],
+            ],
+            &[
+                &[false, false, false, false],
+                &[false, false, false, false],
+                &[true,  true,  true,  true],
+                &[false, false, false, false],
+            ],
+            &[
+                &[false, true, false, false],
+                &[false, true, false, false],
+                &[false, true, false, false],
+                &[false, true, false, false],
+            ],
+        ],
+        spawn_offset: (1, 2),
+    },
+    PieceData {
+        patterns: &[
+            &[
+                &[false, true, false],
+                &[true, true, true],
+                &[false, false, false],
+            ],
+            &[
+                &[false, true, false],
+                &[false, true, true],
+                &[false, true, false],
+            ],
+            &[
+                &[false, false, false],
+                &[true, true, true],
+                &[false, true, false],
+            ],
+            &[
+                &[false, true, false],
+                &[true, true, false],
+                &[false, true, false],
+            ],
+        ],
+        spawn_offset: (0, 2),
+    },
+    PieceData {
+        patterns: &[
+            &[
+                &[false, false, true],
+                &[true, true, true],
+                &[false, false, false],
+            ],
+            &[
+                &[false, true, false],
+                &[false, true, false],
+                &[false, true, true],
+            ],
+            &[
+                &[false, false, false],
+                &[true, true, true],
+                &[true, false, false],
+            ],
+            &[
+                &[true, true, false],
+                &[false, true, false],
+                &[false, true, false],
+            ],
+        ],
+        spawn_offset: (0, 2),
+    },
+    PieceData {
+        patterns: &[
+            &[
+                &[true, false, false],
+                &[true, true, true],
+                &[false, false, false],
+            ],
+            &[
+                &[false, true, true],
+                &[false, true, false],
+                &[false, true, false],
+            ],
+            &[
+                &[false, false, false],
+                &[true, true, true],
+                &[false, false, true],
+            ],
+            &[
+                &[false, true, false],
+                &[false, true, false],
+                &[true, true, false],
+            ],
+        ],
+        spawn_offset: (0, 2),
+    },
+    PieceData {
+        patterns: &[
+            &[
+                &[true, true],
+                &[true, true],
+            ],
+        ],
+        spawn_offset: (0, 1),
+    },
+    PieceData {
+        patterns: &[
+            &[
+                &[true, true, false],
+                &[false, true, true],
+                &[false, false, false],
+            ],
+            &[
+                &[false, false, true],
+                &[false, true, true],
+                &[false, true, false],
+            ],
+            &[
+                &[false, false, false],
+                &[true, true, false],
+                &[false, true, true],
+            ],
+            &[
+                &[false, true, false],
+                &[true, true, false],
+                &[true, false, false],
+            ],
+        ],
+        spawn_offset: (0, 2),
+    },
+    PieceData {
+        patterns: &[
+            &[
+                &[false, true, true],
+                &[true, true, false],
+                &[false, false, false],
+            ],
+            &[
+                &[false, true, false],
+                &[false, true, true],
+                &[false, false, true],
+            ],
+            &[
+                &[false, false, false],
+                &[false, true, true],
+                &[true, true, false],
+            ],
+            &[
+                &[true, false, false],
+                &[true, true, false],
+                &[false, true, false],
+            ],
+        ],
+        spawn_offset: (0, 2),
+    },
+];
+
+// https://tetris.wiki/Arika_Rotation_System
+#[rustfmt::skip]
+const ARS_DATA: [PieceData; N_PIECES] = [
+    PieceData {
+        patterns: &[
+            &[
+                &[false, false, false, false],
+                &[true, true, true, true],
+                &[false, false, false, false],
+                &[false, false, false, false],
+            ],
+            &[
+                &[false, false, true, false],
+                &[false, false, true, false],
+                &[false, false, true, false],
+                &[false, false, true, false],
+            ],
+        ],
+        spawn_offset: (1, 2),
+    },
+    PieceData {
+        patterns: &[
+            &[
+                &[false, false, false],
+                &[true, true, true],
+                &[false, true, false],
+            ],
+            &[
+                &[false, true, false],
+                &[true, true, false],
+                &[false, true, false],
+            ],
+            &[
+                &[false, false, false],
+                &[false, true, false],
+                &[true, true, true],
+            ],
+            &[
+                &[false, true, false],
+                &[false, true, true],
+                &[false, true, false],
+            ],
+        ],
+        spawn_offset: (1, 2),
+    },
+    PieceData {
+        patterns: &[
+            &[
+                &[false, false, false],
+                &[true, true, true],
+                &[true, false, false],
+            ],
+            &[
+                &[true, true, false],
+                &[false, true, false],
+                &[false, true, false],
+            ],
+            &[
+                &[false, false, false],
+                &[false, false, true],
+                &[true, true, true],
+            ],
+            &[
+                &[false, true, false],
+                &[false, true, false],
+                &[false, true, true],
+            ],
+        ],
+        spawn_offset: (1, 2),
+    },
+    PieceData {
+        patterns: &[
+            &[
+                &[false, false, false],
+                &[true, true, true],
+                &[false, false, true],
+            ],
+            &[
+                &[false, true, false],
+                &[false, true, false],
+                &[true, true, false],
+            ],
+            &[
+                &[false, false, false],
+                &[true, false, false],
+                &[true, true, true],
+            ],
+            &[
+                &[false, true, true],
+                &[false, true, false],
+                &[false, true, false],
+            ],
+        ],
+        spawn_offset: (1, 2),
+    },
+    PieceData {
+        patterns: &[
+            &[
+                &[true, true],
+                &[true, true],
+            ],
+        ],
+        spawn_offset: (0, 1),
+    },
+    PieceData {
+        patterns: &[
+            &[
+                &[false, false, false],
+                &[true, true, false],
+                &[false, true, true],
+            ],
+            &[
+                &[false, false, true],
+                &[false, true, true],
+                &[false, true, false],
+            ],
+        ],
+        spawn_offset: (1, 2),
+    },
+    PieceData {
+        patterns: &[
+            &[
+                &[false, false, false],
+                &[false, true, true],
+                &[true, true, false],
+            ],
+            &[
+                &[true, false, false],
+                &[true, true, false],
+                &[false, true, false],
+            ],
+        ],
+        spawn_offset: (1, 2),
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece::Piece;
+
+    const KINDS: [RotationSystemKind; 4] = [
+        RotationSystemKind::Nes,
+        RotationSystemKind::Srs,
+        RotationSystemKind::Ars,
+        RotationSystemKind::SrsNoKicks,
+    ];
+
+    #[test]
+    fn test_spawn_centered() {
+        let board = Board::default();
+        for kind in KINDS {
+            let system = kind.system();
+            assert_eq!(system.name().parse(), Ok(kind));
+            for index in 0..N_PIECES {
+                let start = Piece::from_index(index)
+                    .with_rotation_system(system)
+                    .into_start_move(board.size());
+                assert!(start.is_valid(&board), "{start:?}");
+                let pattern = start.pattern();
+                let cols = (0..pattern.cols())
+                    .filter(|&c| (0..pattern.rows()).any(|r| pattern.filled(r, c)))
+                    .map(|c| start.pos.col + c as isize)
+                    .collect::<Vec<_>>();
+                let rows = (0..pattern.rows())
+                    .filter(|&r| pattern.row(r) != 0)
+                    .map(|r| start.pos.row + r as isize)
+                    .collect::<Vec<_>>();
+                // the piece is in the middle columns and touches the top row
+                assert!(cols.contains(&4) && cols.contains(&5), "{start:?}");
+                assert_eq!(rows[0], 0, "{start:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_ars_center_column() {
+        let mut rows = vec![".........."; 17];
+        rows.extend(["....#.....", "..........", ".........."]);
+        let board = Board::from_str(&rows.join("\n")).unwrap();
+        let t = Piece::from_name('T').unwrap().with_rotation_system(&Ars);
+        // pointing down in rows 18 and 19, rotating would overlap the cell in the center column
+        let r#move = Move {
+            piece: t,
+            pos: Position {
+                rot: 0,
+                row: 17,
+                col: 3,
+            },
+        };
+        assert!(r#move.is_valid(&board));
+        assert!(r#move.rotate(&board, 1).is_none());
+        // a blocked cell at the side kicks the piece instead
+        let r#move = Move {
+            piece: t,
+            pos: Position {
+                rot: 3,
+                row: 10,
+                col: -1,
+            },
+        };
+        assert!(r#move.is_valid(&board));
+        assert_eq!(r#move.rotate(&board, 0).unwrap().pos.col, 0);
+    }
+
+    #[test]
+    fn test_srs_kicks_reversible() {
+        // rotating back tries the opposite offsets in the same order
+        for piece in 0..N_PIECES {
+            let system = Srs;
+            let rotations = system.patterns(piece).len();
+            for from in 0..rotations {
+                let to = (from + 1) % rotations;
+                let forward = system.kicks(piece, from, to);
+                let backward = system.kicks(piece, to, from);
+                assert_eq!(forward.len(), backward.len());
+                for (a, b) in forward.iter().zip(backward) {
+                    assert_eq!((a.0, a.1), (-b.0, -b.1));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use crate::tspin::TSpin;
+use serde::Serialize;
+use std::str::FromStr;
+
+/// Used to select how points are awarded for clearing rows.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub enum ScoringKind {
+    /// The scoring of NES Tetris, which only rewards the number of cleared rows.
+    #[default]
+    Nes,
+    /// The scoring of modern guideline games, which also rewards T-spins, combos,
+    /// back-to-back clears, perfect clears and dropping pieces.
+    Guideline,
+}
+
+/// What happened when a piece was placed, as far as scoring is concerned.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Clear {
+    pub rows: usize,
+    pub tspin: Option<TSpin>,
+    /// The number of consecutive placements that cleared rows, including this one.
+    pub combo: u64,
+    /// Whether this is a difficult clear that directly follows another difficult clear.
+    pub back_to_back: bool,
+    /// Whether the board is empty after clearing the rows.
+    pub perfect: bool,
+}
+
+impl Clear {
+    /// Tetrises and T-spins that clear rows are difficult, which can be chained back-to-back.
+    pub fn difficult(&self) -> bool {
+        self.rows == 4 || (self.rows > 0 && self.tspin.is_some())
+    }
+}
+
+const NES_POINTS: [u64; 5] = [0, 40, 100, 300, 1200];
+
+const GUIDELINE_POINTS: [u64; 5] = [0, 100, 300, 500, 800];
+const GUIDELINE_TSPIN_MINI_POINTS: [u64; 3] = [100, 200, 400];
+const GUIDELINE_TSPIN_POINTS: [u64; 4] = [400, 800, 1200, 1600];
+const GUIDELINE_PERFECT_CLEAR_POINTS: [u64; 5] = [0, 800, 1200, 1800, 2000];
+const GUIDELINE_BACK_TO_BACK_TETRIS_PERFECT_CLEAR_POINTS: u64 = 3200;
+const GUIDELINE_COMBO_POINTS: u64 = 50;
+
+impl ScoringKind {
+    /// Returns the points for placing a piece at the given level, which starts at 0.
+    pub fn points(&self, clear: &Clear, level: u64) -> u64 {
+        let points = match self {
+            ScoringKind::Nes => NES_POINTS[clear.rows],
+            ScoringKind::Guideline => {
+                let mut points = match clear.tspin {
+                    None => GUIDELINE_POINTS[clear.rows],
+                    Some(TSpin::Mini) => GUIDELINE_TSPIN_MINI_POINTS[clear.rows.min(2)],
+                    Some(TSpin::Full) => GUIDELINE_TSPIN_POINTS[clear.rows.min(3)],
+                };
+                if clear.back_to_back {
+                    points = points * 3 / 2;
+                }
+                if clear.combo > 1 {
+                    points += GUIDELINE_COMBO_POINTS * (clear.combo - 1);
+                }
+                if clear.perfect {
+                    points += if clear.back_to_back && clear.rows == 4 {
+                        GUIDELINE_BACK_TO_BACK_TETRIS_PERFECT_CLEAR_POINTS
+                    } else {
+                        GUIDELINE_PERFECT_CLEAR_POINTS[clear.rows]
+                    };
+                }
+                points
+            }
+        };
+        points * (level + 1)
+    }
+
+    /// Returns the points for dropping a piece by the given number of rows.
+    /// Only guideline scoring rewards dropping, with 1 point per row for a soft drop
+    /// and 2 points per row for a hard drop.
+    pub fn drop_points(&self, rows: u64, hard: bool) -> u64 {
+        match self {
+            ScoringKind::Nes => 0,
+            ScoringKind::Guideline => rows * if hard { 2 } else { 1 },
+        }
+    }
+}
+
+impl FromStr for ScoringKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nes" => Ok(ScoringKind::Nes),
+            "guideline" => Ok(ScoringKind::Guideline),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guideline_points() {
+        let scoring = ScoringKind::Guideline;
+        let tetris = Clear {
+            rows: 4,
+            combo: 1,
+            ..Default::default()
+        };
+        assert_eq!(scoring.points(&tetris, 0), 800);
+        assert_eq!(scoring.points(&tetris, 1), 1600);
+        let back_to_back = Clear {
+            back_to_back: true,
+            combo: 3,
+            ..tetris
+        };
+        assert_eq!(scoring.points(&back_to_back, 0), 1200 + 100);
+        let tspin_double = Clear {
+            rows: 2,
+            tspin: Some(TSpin::Full),
+            combo: 1,
+            ..Default::default()
+        };
+        assert!(tspin_double.difficult());
+        assert_eq!(scoring.points(&tspin_double, 0), 1200);
+        let perfect_single = Clear {
+            rows: 1,
+            combo: 1,
+            perfect: true,
+            ..Default::default()
+        };
+        assert_eq!(scoring.points(&perfect_single, 0), 100 + 800);
+        // NES scoring ignores everything but the rows
+        assert_eq!(ScoringKind::Nes.points(&back_to_back, 0), 1200);
+    }
+}
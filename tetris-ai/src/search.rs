@@ -0,0 +1,391 @@
+use crate::{
+    cache::BoardCache,
+    feature::Weights,
+    r#move::{Move, MoveGenerator, Position},
+    piece::{N_PIECES, Piece},
+    state::State,
+};
+use serde::Serialize;
+use std::str::FromStr;
+
+const DEFAULT_DEPTH: usize = 1;
+const DEFAULT_WIDTH: usize = 10;
+
+/// How the simulator searches for the best move.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SearchMode {
+    /// Only looks at the landing positions of the current piece.
+    #[default]
+    Greedy,
+    /// Also places the next `depth` pieces of the preview queue, only the best `width`
+    /// placements of each piece are searched further.
+    Lookahead { depth: usize, width: usize },
+    /// Averages the best placement of every possible next piece, weighted by the chance
+    /// the randomizer generates it. Only the best `width` placements are searched further.
+    Expectimax { width: usize },
+    /// Places the next `depth` pieces of the preview queue, keeping only the best `width`
+    /// boards after each piece.
+    Beam { depth: usize, width: usize },
+}
+
+impl FromStr for SearchMode {
+    type Err = ();
+
+    /// Parses a search mode, e.g. `greedy`, `lookahead:2:5`, `expectimax:5` or `beam:3:20`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let mode = match parts.next() {
+            Some("greedy") => SearchMode::Greedy,
+            Some("lookahead") => {
+                let depth = parse_param(parts.next(), DEFAULT_DEPTH)?;
+                let width = parse_param(parts.next(), DEFAULT_WIDTH)?;
+                if width == 0 {
+                    return Err(());
+                }
+                SearchMode::Lookahead { depth, width }
+            }
+            Some("expectimax") => {
+                let width = parse_param(parts.next(), DEFAULT_WIDTH)?;
+                if width == 0 {
+                    return Err(());
+                }
+                SearchMode::Expectimax { width }
+            }
+            Some("beam") => {
+                let depth = parse_param(parts.next(), DEFAULT_DEPTH)?;
+                let width = parse_param(parts.next(), DEFAULT_WIDTH)?;
+                if width == 0 {
+                    return Err(());
+                }
+                SearchMode::Beam { depth, width }
+            }
+            _ => return Err(()),
+        };
+        if parts.next().is_some() {
+            return Err(());
+        }
+        Ok(mode)
+    }
+}
+
+fn parse_param(param: Option<&str>, default: usize) -> Result<usize, ()> {
+    param.map_or(Ok(default), |p| p.parse().map_err(|_| ()))
+}
+
+/// Statistics about the search performed in a single step.
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct SearchStats {
+    /// The number of placements that were evaluated.
+    pub nodes: u64,
+    /// The time spent searching in microseconds, always 0 on wasm as there is no clock.
+    pub micros: u64,
+}
+
+/// A state in the search tree, with the cache of its board when evaluating incrementally.
+#[derive(Debug, Clone)]
+pub(crate) struct Node {
+    pub(crate) state: State,
+    pub(crate) cache: Option<BoardCache>,
+}
+
+/// A landing position of a piece, with the node after placing it and its evaluation.
+pub(crate) type Expansion = (Position, Node, f64);
+
+/// The buffers used while searching, which are kept between searches so that searching
+/// does not allocate once they have grown large enough.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SearchBuffers {
+    /// The unused buffers of expansions, a search takes one for every piece it is placing at the same time.
+    expansions: Vec<Vec<Expansion>>,
+    /// The states in the beam and their children, with the index of the root they were reached from.
+    beam: Vec<(usize, Node, f64)>,
+    children: Vec<(usize, Node, f64)>,
+}
+
+/// Searches the placements of pieces, counting the number of nodes that are expanded.
+pub(crate) struct Searcher<'a> {
+    weights: &'a Weights,
+    generator: &'a mut MoveGenerator,
+    buffers: &'a mut SearchBuffers,
+    time_pressure: bool,
+    /// Whether to evaluate placements from the cache of the parent board, see [`BoardCache`].
+    incremental: bool,
+    nodes: u64,
+}
+
+impl<'a> Searcher<'a> {
+    pub(crate) fn new(
+        weights: &'a Weights,
+        generator: &'a mut MoveGenerator,
+        buffers: &'a mut SearchBuffers,
+        time_pressure: bool,
+    ) -> Self {
+        Self {
+            weights,
+            generator,
+            buffers,
+            time_pressure,
+            incremental: false,
+            nodes: 0,
+        }
+    }
+
+    /// Evaluates the features that support it from the cache of the parent board,
+    /// which is updated for each placement instead of scanning the whole board.
+    pub(crate) fn with_incremental(mut self, incremental: bool) -> Self {
+        self.incremental = incremental;
+        self
+    }
+
+    pub(crate) fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /// Evaluates the states after every landing position of a piece.
+    /// The expansions should be given back with [`Searcher::recycle`] when they are no longer needed.
+    pub(crate) fn expand(&mut self, node: &Node, piece: Piece) -> Vec<Expansion> {
+        let mut expansions = self.buffers.expansions.pop().unwrap_or_default();
+        Self::expand_with(
+            self.weights,
+            self.generator,
+            self.time_pressure,
+            self.incremental,
+            &mut self.nodes,
+            &node.state,
+            node.cache.as_ref(),
+            piece,
+            &mut expansions,
+        );
+        expansions
+    }
+
+    /// Like [`Searcher::expand`], but starts from a state outside of the search tree and leaves
+    /// the landing positions in the given generator, so that the path to one of them can be materialized afterwards.
+    pub(crate) fn expand_into(
+        &mut self,
+        generator: &mut MoveGenerator,
+        state: &State,
+        piece: Piece,
+    ) -> Vec<Expansion> {
+        let mut expansions = self.buffers.expansions.pop().unwrap_or_default();
+        Self::expand_with(
+            self.weights,
+            generator,
+            self.time_pressure,
+            self.incremental,
+            &mut self.nodes,
+            state,
+            None,
+            piece,
+            &mut expansions,
+        );
+        expansions
+    }
+
+    /// Gives back the buffer of expansions, so that it can be reused.
+    pub(crate) fn recycle(&mut self, mut expansions: Vec<Expansion>) {
+        expansions.clear();
+        self.buffers.expansions.push(expansions);
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn expand_with(
+        weights: &Weights,
+        generator: &mut MoveGenerator,
+        time_pressure: bool,
+        incremental: bool,
+        nodes: &mut u64,
+        state: &State,
+        cache: Option<&BoardCache>,
+        piece: Piece,
+        expansions: &mut Vec<Expansion>,
+    ) {
+        generator.generate(
+            state.board(),
+            piece,
+            time_pressure.then(|| state.stats().level),
+        );
+        *nodes += generator.destinations().len() as u64;
+        // the nodes in the search tree keep their cache, so only the root computes it from scratch
+        let cache = incremental.then(|| {
+            cache
+                .copied()
+                .unwrap_or_else(|| BoardCache::new(state.board()))
+        });
+        expansions.extend(generator.destinations().iter().map(|&pos| {
+            let future = state.future(Move { piece, pos }, generator.rotated_from(pos));
+            let cache = cache.map(|cache| cache.update(state, &future));
+            let score = match &cache {
+                Some(cache) => weights.evaluate_cached(&future, cache),
+                None => weights.evaluate(&future),
+            };
+            let node = Node {
+                state: future,
+                cache,
+            };
+            (pos, node, score)
+        }));
+    }
+
+    /// Finds the best evaluation after placing all the given pieces in order.
+    /// Returns negative infinity if the pieces cannot all be placed.
+    pub(crate) fn lookahead(&mut self, node: &Node, pieces: &[Piece], width: usize) -> f64 {
+        let Some((&piece, rest)) = pieces.split_first() else {
+            return self.weights.evaluate(&node.state);
+        };
+        let mut candidates = self.expand(node, piece);
+        let best = if rest.is_empty() {
+            candidates
+                .iter()
+                .map(|(_, _, score)| *score)
+                .fold(f64::NEG_INFINITY, f64::max)
+        } else {
+            keep_best(&mut candidates, width);
+            candidates
+                .iter()
+                .map(|(_, future, _)| self.lookahead(future, rest, width))
+                .fold(f64::NEG_INFINITY, f64::max)
+        };
+        self.recycle(candidates);
+        best
+    }
+
+    /// Finds the expected best evaluation after placing the next piece,
+    /// given the probability of each piece coming next.
+    pub(crate) fn expectimax(&mut self, node: &Node, probabilities: &[f64; N_PIECES]) -> f64 {
+        probabilities
+            .iter()
+            .enumerate()
+            .filter(|(_, probability)| **probability > 0.0)
+            .map(|(index, probability)| {
+                let piece =
+                    Piece::from_index(index).with_rotation_system(node.state.rotation_system());
+                probability * self.lookahead(node, &[piece], 1)
+            })
+            .sum()
+    }
+
+    /// Places the given pieces in order, keeping only the best `width` states after each piece.
+    /// Replaces the score of each root by the best evaluation reached from it,
+    /// negative infinity if the root did not survive in the beam.
+    pub(crate) fn beam<T>(&mut self, roots: &mut [(Node, T, f64)], pieces: &[Piece], width: usize) {
+        let mut beam = std::mem::take(&mut self.buffers.beam);
+        let mut children = std::mem::take(&mut self.buffers.children);
+        beam.clear();
+        beam.extend(
+            roots
+                .iter()
+                .enumerate()
+                .map(|(root, (node, _, score))| (root, node.clone(), *score)),
+        );
+        for &piece in pieces {
+            children.clear();
+            for (root, node, _) in beam.iter() {
+                let mut expansions = self.expand(node, piece);
+                children.extend(
+                    expansions
+                        .drain(..)
+                        .map(|(_, future, score)| (*root, future, score)),
+                );
+                self.recycle(expansions);
+            }
+            if children.is_empty() {
+                break; // none of the states can place the piece, keep the last beam
+            }
+            keep_best(&mut children, width);
+            std::mem::swap(&mut beam, &mut children);
+        }
+        for (_, _, score) in roots.iter_mut() {
+            *score = f64::NEG_INFINITY;
+        }
+        for (root, _, score) in beam.iter() {
+            roots[*root].2 = roots[*root].2.max(*score);
+        }
+        self.buffers.beam = beam;
+        self.buffers.children = children;
+    }
+}
+
+/// Keeps only the best `width` states, sorted from best to worst score.
+/// States with equal scores keep their order, like a stable sort, but nothing is allocated.
+pub(crate) fn keep_best<T, U>(states: &mut Vec<(T, U, f64)>, width: usize) {
+    if width == 0 {
+        states.clear();
+        return;
+    }
+    let width = width.min(states.len());
+    let insert_at = |states: &[(T, U, f64)], score: f64| {
+        states.partition_point(|state| state.2.total_cmp(&score).is_ge())
+    };
+    // insertion sort the first states, then insert the better states after them
+    for i in 1..width {
+        let at = insert_at(&states[..i], states[i].2);
+        states[at..=i].rotate_right(1);
+    }
+    for i in width..states.len() {
+        if states[i].2.total_cmp(&states[width - 1].2).is_gt() {
+            let at = insert_at(&states[..width - 1], states[i].2);
+            states.swap(i, width - 1);
+            states[at..width].rotate_right(1);
+        }
+    }
+    states.truncate(width);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::seeded_rng;
+    use rand::Rng;
+
+    #[test]
+    fn test_parse_search_mode() {
+        assert_eq!("greedy".parse(), Ok(SearchMode::Greedy));
+        assert_eq!(
+            "lookahead".parse(),
+            Ok(SearchMode::Lookahead {
+                depth: DEFAULT_DEPTH,
+                width: DEFAULT_WIDTH
+            })
+        );
+        assert_eq!(
+            "lookahead:2:5".parse(),
+            Ok(SearchMode::Lookahead { depth: 2, width: 5 })
+        );
+        assert_eq!(
+            "expectimax:3".parse(),
+            Ok(SearchMode::Expectimax { width: 3 })
+        );
+        assert_eq!(
+            "beam:3:20".parse(),
+            Ok(SearchMode::Beam {
+                depth: 3,
+                width: 20
+            })
+        );
+        assert!("lookahead:1:0".parse::<SearchMode>().is_err());
+        assert!("expectimax:1:2".parse::<SearchMode>().is_err());
+        assert!("lookahead:x".parse::<SearchMode>().is_err());
+        assert!("greedy:1".parse::<SearchMode>().is_err());
+    }
+
+    #[test]
+    fn test_keep_best() {
+        let mut rng = seeded_rng(Some(0));
+        for _ in 0..100 {
+            let len = rng.random_range(0..40);
+            // few distinct scores, so that there are many ties
+            let states = (0..len)
+                .map(|i| (i, (), rng.random_range(0..5) as f64))
+                .collect::<Vec<_>>();
+            for width in [0, 1, 3, 10, 50] {
+                let mut expected = states.clone();
+                expected.sort_by(|a, b| b.2.total_cmp(&a.2));
+                expected.truncate(width);
+                let mut best = states.clone();
+                keep_best(&mut best, width);
+                assert_eq!(best, expected);
+            }
+        }
+    }
+}
@@ -1,25 +1,39 @@
-use crate::board::Board;
+use crate::board::{Board, BoardSize};
 use crate::feature::{Weights, WeightsMap};
-use crate::r#move::{Path, move_dijkstra};
-use crate::rng::gen_random_piece;
+use crate::r#move::Path;
+use crate::policy::{Decision, Explanation, LinearPolicy, Policy};
+use crate::queue::MAX_PREVIEW;
+use crate::rng::{GameRng, Randomizer, RandomizerKind, seeded_rng};
+use crate::rotation::{Nes, RotationSystem, RotationSystemKind};
+use crate::scoring::ScoringKind;
+use crate::search::{SearchMode, SearchStats};
 use crate::state::{State, Stats};
-use rand::Rng;
+use crate::timing::Timing;
 #[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub struct Simulator {
     state: State,
-    weights: Weights,
+    linear: LinearPolicy,
+    policy: Option<Box<dyn Policy>>,
     current_path: Option<Path>,
     time_pressure: bool,
+    seed: Option<u64>,
+    rng: GameRng,
+    randomizer: Box<dyn Randomizer>,
+    preview: usize,
+    hold: bool,
+    board_size: BoardSize,
+    rotation_system: &'static dyn RotationSystem,
+    scoring: ScoringKind,
 }
 
 impl Simulator {
-    pub fn new_with_weights(weights: Weights) -> Self {
+    pub fn new_with_weights(weights: Weights, seed: Option<u64>) -> Self {
         Self {
-            weights,
-            ..Self::default()
+            linear: LinearPolicy::new(weights, SearchMode::default()),
+            ..Self::new(seed)
         }
     }
 
@@ -30,36 +44,115 @@ impl Simulator {
     pub fn board(&self) -> &Board {
         self.state.board()
     }
+
+    /// Sets the randomizer that generates the pieces, this resets the simulator.
+    pub fn set_randomizer(&mut self, randomizer: Box<dyn Randomizer>) {
+        self.randomizer = randomizer;
+        self.reset();
+    }
+
+    /// Sets the size of the board, this resets the simulator.
+    pub fn set_board_size(&mut self, board_size: BoardSize) {
+        self.board_size = board_size;
+        self.reset();
+    }
+
+    /// Sets the rotation system of the pieces, this resets the simulator.
+    pub fn set_rotation_system(&mut self, rotation_system: &'static dyn RotationSystem) {
+        self.rotation_system = rotation_system;
+        self.reset();
+    }
+
+    /// Sets how points are awarded for clearing rows, this resets the simulator.
+    pub fn set_scoring(&mut self, scoring: ScoringKind) {
+        self.scoring = scoring;
+        self.reset();
+    }
+
+    /// Sets how the time it takes to move a piece is modelled when generating moves.
+    pub fn set_timing(&mut self, timing: Timing) {
+        self.linear.set_timing(timing);
+    }
+
+    /// Replaces the linear weights policy with another policy to decide the moves.
+    pub fn set_policy(&mut self, policy: Box<dyn Policy>) {
+        self.policy = Some(policy);
+    }
+
+    /// Returns statistics about the search performed in the last step.
+    pub fn search_stats(&self) -> SearchStats {
+        self.policy().search_stats()
+    }
+
+    /// Returns why the placement of the last step was chosen, if explaining is enabled
+    /// with [`Simulator::set_explain`] and the policy can explain it.
+    pub fn explanation(&self) -> Option<&Explanation> {
+        self.policy().explanation()
+    }
+
+    /// Evaluates the features of the linear weights policy incrementally, see [`LinearPolicy::set_incremental`].
+    pub fn set_incremental(&mut self, incremental: bool) {
+        self.linear.set_incremental(incremental);
+    }
+
+    /// Sets how the linear weights policy searches for the best move.
+    /// Searching ahead uses the preview queue, see [`Simulator::set_preview`].
+    pub fn set_search(&mut self, search: SearchMode) {
+        self.linear.set_search(search);
+    }
+
+    fn policy(&self) -> &dyn Policy {
+        self.policy.as_deref().unwrap_or(&self.linear)
+    }
 }
 
 impl Default for Simulator {
     fn default() -> Self {
         Self {
             state: State::default(),
-            weights: Weights::default(),
+            linear: LinearPolicy::default(),
+            policy: None,
             current_path: None,
             time_pressure: true,
+            seed: None,
+            rng: seeded_rng(None),
+            randomizer: RandomizerKind::default().build(),
+            preview: 0,
+            hold: false,
+            board_size: BoardSize::default(),
+            rotation_system: &Nes,
+            scoring: ScoringKind::default(),
         }
     }
 }
 
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl Simulator {
+    /// Creates a new simulator, if a seed is given the games it plays are reproducible.
     #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
-    pub fn new() -> Self {
-        Self::default()
+    pub fn new(seed: Option<u64>) -> Self {
+        Self {
+            seed,
+            rng: seeded_rng(seed),
+            ..Self::default()
+        }
     }
 
-    pub fn new_with_preset(preset: &str) -> Self {
+    pub fn new_with_preset(preset: &str, seed: Option<u64>) -> Self {
         Self {
-            weights: Weights::from_preset(preset),
-            ..Self::default()
+            linear: LinearPolicy::new(Weights::from_preset(preset), SearchMode::default()),
+            ..Self::new(seed)
         }
     }
 
     pub fn reset(&mut self) {
-        self.state = State::default();
+        self.state = State::new(Board::new(self.board_size))
+            .with_rotation_system(self.rotation_system)
+            .with_scoring(self.scoring)
+            .with_preview(self.preview);
         self.current_path = None;
+        self.rng = seeded_rng(self.seed);
+        self.randomizer.reset();
     }
 
     pub fn run(&mut self) {
@@ -75,38 +168,40 @@ impl Simulator {
     }
 
     pub fn step(&mut self) -> bool {
-        let piece = gen_random_piece(self.state.delta().map(|d| d.r#move.piece.index()));
-
-        // Use resivoir sampling to ramdomly select one of the best possible moves
-        let mut chosen = None;
-        let mut best_score = f64::NEG_INFINITY;
-        let mut count = 0;
-        let mut rng = rand::rng();
-        for path in move_dijkstra(
-            self.state.board(),
-            piece,
-            if self.time_pressure {
-                Some(self.state.stats().level)
-            } else {
-                None
-            },
-        ) {
-            let future = self.state.future(path.final_move());
-            let score = self.weights.evaluate(&future);
-            if score > best_score {
-                best_score = score;
-                chosen = Some((future, path));
-                count = 1;
-            } else if score == best_score {
-                count += 1;
-                if rng.random_range(0..count) == 0 {
-                    chosen = Some((future, path));
-                }
-            }
+        let mut piece = self.state.next_piece(&mut *self.randomizer, &mut self.rng);
+        if self.hold && self.state.hold().is_none() {
+            // start by holding the first piece, so there is always a piece to swap with
+            self.state.swap_hold(piece);
+            piece = self.state.next_piece(&mut *self.randomizer, &mut self.rng);
         }
 
-        if let Some((next, path)) = chosen {
-            self.state = next; // update state
+        let hold = if self.hold { self.state.hold() } else { None };
+        let policy: &mut dyn Policy = match self.policy.as_deref_mut() {
+            Some(policy) => policy,
+            None => &mut self.linear,
+        };
+        let chosen = policy.choose(Decision {
+            state: &self.state,
+            piece,
+            queue: self.state.queue().pieces(),
+            hold,
+            probabilities: self.randomizer.probabilities(),
+            time_pressure: self.time_pressure,
+            rng: &mut self.rng,
+        });
+
+        if let Some(path) = chosen {
+            let placed = path.final_move();
+            if placed.piece != piece {
+                // swap the current piece with the held piece that was placed instead
+                assert_eq!(
+                    hold,
+                    Some(placed.piece),
+                    "policy placed an unavailable piece"
+                );
+                self.state.swap_hold(piece);
+            }
+            self.state = self.state.future(placed, path.rotated_from()); // update state
             self.current_path = Some(path);
             return true;
         }
@@ -115,22 +210,396 @@ impl Simulator {
     }
 
     pub fn update_weights(&mut self, weights_map: WeightsMap) {
-        self.weights = weights_map.into();
+        self.linear.set_weights(weights_map.into());
     }
 
     pub fn set_time_pressure(&mut self, time_pressure: bool) {
         self.time_pressure = time_pressure;
     }
 
+    /// Selects how the time it takes to move a piece is modelled by name, see [`Timing`].
+    /// With time pressure, the frame-exact timings use the gravity of the current level.
+    pub fn select_timing(&mut self, name: &str) {
+        let Ok(timing) = name.parse::<Timing>() else {
+            panic!("Unknown timing: '{}'", name);
+        };
+        self.set_timing(timing);
+    }
+
+    /// Allows the simulator to swap the current piece with the held piece, this resets the simulator.
+    pub fn set_hold(&mut self, hold: bool) {
+        self.hold = hold;
+        self.reset();
+    }
+
+    /// Sets the number of upcoming pieces that are previewed (0 to 6), this resets the simulator.
+    pub fn set_preview(&mut self, preview: usize) {
+        assert!(
+            preview <= MAX_PREVIEW,
+            "preview must be at most {MAX_PREVIEW}"
+        );
+        self.preview = preview;
+        self.reset();
+    }
+
+    /// Allows the linear weights policy to rotate pieces by 180 degrees, like a human player
+    /// can with [`Game::set_rotate_180`](crate::game::Game::set_rotate_180).
+    pub fn set_rotate_180(&mut self, enabled: bool) {
+        self.linear.set_rotate_180(enabled);
+    }
+
+    /// Enables or disables explaining the placements chosen by the linear weights policy,
+    /// which lists the features of every placement that was considered.
+    pub fn set_explain(&mut self, explain: bool) {
+        self.linear.set_explain(explain);
+    }
+
+    /// Selects a search mode by name, see [`SearchMode`] for the available names.
+    pub fn select_search(&mut self, name: &str) {
+        let Ok(search) = name.parse::<SearchMode>() else {
+            panic!("Unknown search mode: '{}'", name);
+        };
+        self.set_search(search);
+    }
+
+    /// Selects a randomizer by name, see [`RandomizerKind`] for the available names.
+    pub fn select_randomizer(&mut self, name: &str) {
+        let Ok(kind) = name.parse::<RandomizerKind>() else {
+            panic!("Unknown randomizer: '{}'", name);
+        };
+        self.set_randomizer(kind.build());
+    }
+
+    /// Selects a board size by name, e.g. `10x20` or `10x20+20`, see [`BoardSize`].
+    pub fn select_board_size(&mut self, name: &str) {
+        let Ok(board_size) = name.parse::<BoardSize>() else {
+            panic!("Unknown board size: '{}'", name);
+        };
+        self.set_board_size(board_size);
+    }
+
+    /// Selects a rotation system by name, see [`RotationSystemKind`] for the available names.
+    pub fn select_rotation_system(&mut self, name: &str) {
+        let Ok(kind) = name.parse::<RotationSystemKind>() else {
+            panic!("Unknown rotation system: '{}'", name);
+        };
+        self.set_rotation_system(kind.system());
+    }
+
+    /// Selects a scoring ruleset by name, see [`ScoringKind`] for the available names.
+    pub fn select_scoring(&mut self, name: &str) {
+        let Ok(scoring) = name.parse::<ScoringKind>() else {
+            panic!("Unknown scoring: '{}'", name);
+        };
+        self.set_scoring(scoring);
+    }
+
     #[cfg(feature = "wasm")]
     #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn state(&self) -> JsValue {
         self.state.js_value()
     }
 
+    #[cfg(feature = "wasm")]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter, js_name = searchStats))]
+    pub fn js_search_stats(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.search_stats()).unwrap()
+    }
+
+    #[cfg(feature = "wasm")]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter, js_name = explanation))]
+    pub fn js_explanation(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.explanation()).unwrap()
+    }
+
     #[cfg(feature = "wasm")]
     #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn path(&self) -> Option<Path> {
         self.current_path.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::r#move::Position;
+    use crate::piece::Piece;
+    use crate::policy::RandomPolicy;
+    use crate::rng::Uniform;
+    use std::str::FromStr;
+
+    /// A simulator that places its first piece on a board with the given bottom rows.
+    fn simulator_on(search: &str, randomizer: &str, preview: usize, rows: &[&str]) -> Simulator {
+        let mut sim = Simulator::new_with_preset("score", Some(0));
+        sim.select_randomizer(randomizer);
+        sim.set_preview(preview);
+        sim.select_search(search);
+        let mut lines = vec![".........."; 20 - rows.len()];
+        lines.extend(rows);
+        sim.state = State::new(Board::from_str(&lines.join("\n")).unwrap()).with_preview(preview);
+        sim
+    }
+
+    /// Returns the position of the first piece placed on a board with the given bottom rows.
+    fn first_position(search: &str, randomizer: &str, preview: usize, rows: &[&str]) -> Position {
+        let mut sim = simulator_on(search, randomizer, preview, rows);
+        assert!(sim.step());
+        sim.state.delta().unwrap().r#move.pos
+    }
+
+    #[test]
+    fn test_seed_reproducible() {
+        let mut a = Simulator::new_with_preset("score", Some(42));
+        let mut b = Simulator::new_with_preset("score", Some(42));
+        a.run_for(200);
+        b.run_for(200);
+        assert_eq!(a.board().to_string(), b.board().to_string());
+        assert_eq!(a.stats().score, b.stats().score);
+
+        // resetting replays the same game
+        let stats = a.stats();
+        a.reset();
+        a.run_for(200);
+        assert_eq!(a.stats().steps, stats.steps);
+        assert_eq!(a.stats().score, stats.score);
+    }
+
+    #[test]
+    fn test_fixed_randomizer() {
+        let mut sim = Simulator::new_with_preset("score", Some(0));
+        sim.select_randomizer("fixed:O");
+        sim.run_for(5);
+        assert_eq!(sim.state.delta().unwrap().r#move.piece.index(), 4);
+    }
+
+    #[test]
+    fn test_incremental() {
+        let mut full = Simulator::new_with_preset("score", Some(7));
+        let mut incremental = Simulator::new_with_preset("score", Some(7));
+        incremental.set_incremental(true);
+        full.run_for(300);
+        incremental.run_for(300);
+        assert_eq!(full.board().to_string(), incremental.board().to_string());
+        assert_eq!(full.stats().score, incremental.stats().score);
+    }
+
+    #[test]
+    fn test_explanation() {
+        let mut sim = Simulator::new_with_preset("score", Some(0));
+        sim.step();
+        assert!(sim.explanation().is_none());
+        sim.set_explain(true);
+        for _ in 0..20 {
+            sim.step();
+            let explanation = sim.explanation().unwrap();
+            let chosen = &explanation.candidates[explanation.chosen.unwrap()];
+            assert_eq!(chosen.r#move, sim.state.delta().unwrap().r#move);
+            // a greedy search chooses one of the best evaluations
+            assert_eq!(explanation.rank, 1);
+            for candidate in &explanation.candidates {
+                let total = candidate
+                    .features
+                    .iter()
+                    .map(|feature| feature.contribution)
+                    .sum::<f64>();
+                assert!((total - candidate.evaluation).abs() < 1e-9);
+                assert!(candidate.evaluation <= chosen.evaluation);
+            }
+        }
+
+        // searching ahead does not choose the best evaluation of the Z on its own, see `test_beam`
+        let rows = ["##.#.....#", ".##..#..##"];
+        let mut sim = simulator_on("beam:1:10", "fixed:ZT", 1, &rows);
+        sim.set_explain(true);
+        assert!(sim.step());
+        let explanation = sim.explanation().unwrap();
+        let chosen = &explanation.candidates[explanation.chosen.unwrap()];
+        assert_eq!(chosen.r#move, sim.state.delta().unwrap().r#move);
+        assert!(explanation.rank > 1);
+        assert!(
+            explanation
+                .candidates
+                .iter()
+                .filter_map(|candidate| candidate.score)
+                .all(|score| score <= chosen.score.unwrap())
+        );
+    }
+
+    #[test]
+    fn test_hold() {
+        let mut sim = Simulator::new_with_preset("score", Some(0));
+        sim.select_randomizer("fixed:IOO");
+        sim.set_hold(true);
+        // the first piece is held, after that the I piece can be swapped for any O piece
+        assert!(sim.step());
+        let mut swaps = 0;
+        for _ in 1..50 {
+            let held = sim.state.hold();
+            assert!(sim.step());
+            let placed = sim.state.delta().unwrap().r#move.piece;
+            if sim.state.hold() != held {
+                // the held piece was placed instead of the current piece, which is held now
+                assert_eq!(Some(placed), held);
+                assert_ne!(sim.state.hold(), Some(placed));
+                swaps += 1;
+            }
+        }
+        assert!(swaps > 0, "the held piece was never placed");
+        assert_eq!(sim.stats().steps, 50);
+    }
+
+    #[test]
+    fn test_board_sizes() {
+        for name in ["10x20+20", "4x20", "6x8", "16x24"] {
+            let mut sim = Simulator::new_with_preset("score", Some(0));
+            sim.select_board_size(name);
+            sim.run_for(100);
+            let size = name.parse::<BoardSize>().unwrap();
+            assert_eq!(sim.board().size(), size);
+            assert!(sim.stats().steps > 0, "no pieces placed on {name}");
+            assert!(sim.board().heights().iter().all(|&h| h <= size.height));
+        }
+    }
+
+    #[test]
+    fn test_srs() {
+        let mut sim = Simulator::new_with_preset("score", Some(0));
+        sim.select_rotation_system("srs");
+        sim.set_preview(1);
+        sim.select_search("expectimax:4");
+        sim.run_for(100);
+        assert_eq!(sim.stats().steps, 100);
+        let piece = sim.state.delta().unwrap().r#move.piece;
+        assert_eq!(piece.rotation_system().name(), "srs");
+    }
+
+    #[test]
+    fn test_rotate_180() {
+        // counts the placements whose path turns the piece by 180 degrees at once
+        let half_turns = |rotate_180: bool| {
+            let mut sim = Simulator::new_with_preset("score", Some(0));
+            sim.select_rotation_system("srs");
+            sim.set_rotate_180(rotate_180);
+            (0..100)
+                .filter(|_| {
+                    sim.step();
+                    let positions = sim.current_path.clone().unwrap().into_moves().concat();
+                    positions
+                        .windows(2)
+                        .any(|pair| pair[0].rot.abs_diff(pair[1].rot) == 2)
+                })
+                .count()
+        };
+        assert_eq!(half_turns(false), 0);
+        assert!(half_turns(true) > 0);
+    }
+
+    #[test]
+    fn test_expectimax() {
+        let mut sim = Simulator::new_with_preset("score", Some(0));
+        sim.select_search("expectimax:4");
+        sim.run_for(20);
+        assert_eq!(sim.stats().steps, 20);
+
+        // without a preview, the first piece is a Z and any piece can come next
+        let rows = ["#########.", ".##.######"];
+        let first_position = |search: &str| {
+            let mut sim = simulator_on(search, "uniform", 0, &rows);
+            sim.randomizer = Box::new(FirstPiece(Piece::from_name('Z')));
+            assert!(sim.step());
+            let r#move = sim.state.delta().unwrap().r#move;
+            assert_eq!(r#move.piece, Piece::from_name('Z').unwrap());
+            r#move.pos
+        };
+        assert_eq!(first_position("greedy").col, 0);
+        assert_eq!(first_position("expectimax:10").col, 4);
+    }
+
+    /// Deals the given piece first, after which every piece is equally likely.
+    struct FirstPiece(Option<Piece>);
+
+    impl Randomizer for FirstPiece {
+        fn next_piece(&mut self, rng: &mut GameRng) -> Piece {
+            self.0.take().unwrap_or_else(|| Uniform.next_piece(rng))
+        }
+
+        fn reset(&mut self) {}
+    }
+
+    #[test]
+    fn test_beam() {
+        let mut sim = Simulator::new_with_preset("score", Some(0));
+        sim.set_preview(3);
+        sim.select_search("beam:3:8");
+        sim.run_for(20);
+        assert_eq!(sim.stats().steps, 20);
+        assert!(sim.search_stats().nodes > 0);
+
+        // a wider beam keeps more boards after each piece, so it expands more nodes
+        let nodes = |search: &str| {
+            let mut sim = Simulator::new_with_preset("score", Some(0));
+            sim.set_preview(3);
+            sim.select_search(search);
+            (0..20)
+                .map(|_| {
+                    sim.step();
+                    sim.search_stats().nodes
+                })
+                .sum::<u64>()
+        };
+        let (narrow, wide) = (nodes("beam:3:2"), nodes("beam:3:8"));
+        assert!(
+            narrow < wide,
+            "{narrow} nodes with width 2, {wide} with width 8"
+        );
+
+        let rows = ["##.#.....#", ".##..#..##"];
+        let greedy = first_position("greedy", "fixed:ZT", 1, &rows);
+        let beam = first_position("beam:1:10", "fixed:ZT", 1, &rows);
+        assert_eq!(greedy.col, 2);
+        assert_eq!(beam.col, 8);
+    }
+
+    #[test]
+    fn test_scripted_policy() {
+        let mut sim = Simulator::new(Some(0));
+        sim.select_randomizer("fixed:O");
+        // stack O pieces in the leftmost columns
+        sim.set_policy(Box::new(|decision: Decision| {
+            decision
+                .placements()
+                .into_iter()
+                .find(|path| path.final_move().pos.col == 0)
+        }));
+        sim.run_for(20);
+        // the last O piece cannot move far enough left in the top row
+        assert_eq!(sim.stats().steps, 9);
+        assert!(sim.state.game_over());
+        assert_eq!(sim.board().height(0), 18);
+        assert_eq!(sim.board().height(2), 0);
+    }
+
+    #[test]
+    fn test_random_policy() {
+        let mut sim = Simulator::new(Some(0));
+        sim.set_policy(Box::new(RandomPolicy));
+        sim.run();
+        assert!(sim.state.game_over());
+    }
+
+    #[test]
+    fn test_lookahead() {
+        let mut sim = Simulator::new_with_preset("score", Some(0));
+        sim.set_preview(2);
+        sim.select_search("lookahead:2:4");
+        sim.run_for(50);
+        assert_eq!(sim.stats().steps, 50);
+
+        // knowing that a J comes next, the O is placed on the left instead of the right
+        let rows = [".###..##.#", "####.#.##."];
+        let greedy = first_position("greedy", "fixed:OJ", 1, &rows);
+        let lookahead = first_position("lookahead:1:10", "fixed:OJ", 1, &rows);
+        assert_eq!(greedy.col, 6);
+        assert_eq!(lookahead.col, 2);
+    }
+}
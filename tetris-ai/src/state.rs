@@ -1,6 +1,17 @@
 #[cfg(feature = "wasm")]
-use crate::board::{BOARD_HEIGHT, BOARD_WIDTH};
-use crate::{board::Board, r#move::Move};
+use crate::board::BoardSize;
+#[cfg(test)]
+use crate::board::ClearedRows;
+use crate::{
+    board::Board,
+    r#move::{Move, Placement, Position},
+    piece::Piece,
+    queue::Queue,
+    rng::{GameRng, Randomizer},
+    rotation::RotationSystem,
+    scoring::{Clear, ScoringKind},
+    tspin::TSpin,
+};
 use serde::Serialize;
 #[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
@@ -12,6 +23,15 @@ pub struct Stats {
     pub score: u64,
     pub level: u64,
     pub tetrises: u64,
+    pub tspins: u64,
+    pub mini_tspins: u64,
+    /// The number of consecutive placements that cleared rows.
+    pub combo: u64,
+    pub max_combo: u64,
+    /// The number of consecutive difficult clears, see [`Clear::difficult`].
+    pub back_to_back: u64,
+    /// The number of placements that emptied the board.
+    pub perfect_clears: u64,
 }
 
 #[derive(Debug, Default, Clone)]
@@ -20,27 +40,35 @@ pub struct State {
     stats: Stats,
     game_over: bool,
     delta: Option<Delta>,
+    queue: Queue,
+    hold: Option<Piece>,
+    scoring: ScoringKind,
 }
 
 #[derive(Debug, Clone)]
 pub struct Delta {
     pub r#move: Move,
     pub eroded: usize,
+    pub tspin: Option<TSpin>,
+    /// Whether the board is empty after clearing the rows.
+    pub perfect_clear: bool,
+    pub placement: Placement,
     #[cfg(test)]
-    pub cleared: Vec<usize>,
+    pub cleared: ClearedRows,
 }
 
 #[cfg(feature = "wasm")]
 #[derive(Serialize)]
 struct WasmState<'a> {
-    board: &'a [[u8; BOARD_WIDTH]; BOARD_HEIGHT],
+    board: Vec<&'a [u8]>,
+    size: BoardSize,
     stats: Stats,
     #[serde(rename = "gameOver")]
     game_over: bool,
+    next: &'a [Piece],
+    hold: Option<Piece>,
 }
 
-const POINTS_PER_CLEARED_ROWS: [u64; 5] = [0, 40, 100, 300, 1200];
-
 impl State {
     pub fn new(board: Board) -> Self {
         Self {
@@ -49,6 +77,32 @@ impl State {
         }
     }
 
+    /// Sets the number of upcoming pieces that are previewed.
+    pub fn with_preview(mut self, size: usize) -> Self {
+        self.queue = Queue::new(size).with_rotation_system(self.queue.rotation_system());
+        self
+    }
+
+    /// Sets the rotation system of the pieces that are dealt.
+    pub fn with_rotation_system(mut self, system: &'static dyn RotationSystem) -> Self {
+        self.queue = self.queue.with_rotation_system(system);
+        self
+    }
+
+    pub fn rotation_system(&self) -> &'static dyn RotationSystem {
+        self.queue.rotation_system()
+    }
+
+    /// Sets how points are awarded for clearing rows.
+    pub fn with_scoring(mut self, scoring: ScoringKind) -> Self {
+        self.scoring = scoring;
+        self
+    }
+
+    pub fn scoring(&self) -> ScoringKind {
+        self.scoring
+    }
+
     pub fn board(&self) -> &Board {
         &self.board
     }
@@ -69,8 +123,37 @@ impl State {
         self.delta.as_ref()
     }
 
+    /// Returns the queue of upcoming pieces.
+    pub fn queue(&self) -> &Queue {
+        &self.queue
+    }
+
+    /// Takes the piece to spawn next from the queue.
+    pub fn next_piece(&mut self, randomizer: &mut dyn Randomizer, rng: &mut GameRng) -> Piece {
+        self.queue.pop(randomizer, rng)
+    }
+
+    /// Returns the piece in the hold slot.
+    pub fn hold(&self) -> Option<Piece> {
+        self.hold
+    }
+
+    /// Puts a piece in the hold slot and returns the piece that was held before.
+    pub fn swap_hold(&mut self, piece: Piece) -> Option<Piece> {
+        self.hold.replace(piece)
+    }
+
+    /// Adds the points for dropping the current piece by the given number of rows.
+    pub fn add_drop_points(&mut self, rows: u64, hard: bool) {
+        self.stats.score += self.scoring.drop_points(rows, hard);
+    }
+
     /// Computes the new 'future' state after a piece has been moved.
-    pub(crate) fn future(&self, r#move: Move) -> Self {
+    /// `rotated_from` is the position the piece was rotated from, if the last action was a rotation,
+    /// which is used to detect T-spins.
+    pub(crate) fn future(&self, r#move: Move, rotated_from: Option<Position>) -> Self {
+        let tspin = TSpin::detect(&self.board, r#move, rotated_from);
+        let placement = Placement::classify(&self.board, r#move, rotated_from);
         let mut board = self.board;
         board.imprint(
             r#move.piece.rotation(r#move.pos.rot),
@@ -80,22 +163,31 @@ impl State {
         );
         let cleared_rows = board.clear_full();
         let cleared = cleared_rows.len();
+        let perfect_clear = cleared > 0 && board.is_empty();
 
         // count the number of cells in the piece that were cleared by the move (eroded cells)
-        let mut eroded = 0;
-        for row in cleared_rows.iter().copied() {
-            let pattern = r#move.pattern();
-            let pos = r#move.pos;
-            if row as isize >= pos.row && (row as isize) < pos.row + pattern.cols() as isize {
-                if let Some(row) = pattern.get_row(row - pos.row as usize) {
-                    for filled in row {
-                        if *filled {
-                            eroded += 1;
-                        }
-                    }
-                }
-            }
-        }
+        let pattern = r#move.pattern();
+        let eroded = cleared_rows
+            .iter()
+            .filter_map(|row| usize::try_from(row as isize - r#move.pos.row).ok())
+            .map(|offset| pattern.row(offset).count_ones() as usize)
+            .sum();
+
+        let combo = if cleared > 0 { self.stats.combo + 1 } else { 0 };
+        let mut clear = Clear {
+            rows: cleared,
+            tspin,
+            combo,
+            back_to_back: false,
+            perfect: perfect_clear,
+        };
+        // only clearing rows that are not difficult breaks a back-to-back chain
+        let back_to_back = match (cleared, clear.difficult()) {
+            (0, _) => self.stats.back_to_back,
+            (_, true) => self.stats.back_to_back + 1,
+            (_, false) => 0,
+        };
+        clear.back_to_back = back_to_back > 1;
 
         let new_lines = self.stats.lines + cleared as u64;
         Self {
@@ -103,17 +195,30 @@ impl State {
             stats: Stats {
                 steps: self.stats.steps + 1,
                 lines: new_lines,
-                score: self.stats.score + POINTS_PER_CLEARED_ROWS[cleared] * (self.stats.level + 1),
+                score: self.stats.score + self.scoring.points(&clear, self.stats.level),
                 level: new_lines / 10,
                 tetrises: self.stats.tetrises + if cleared == 4 { 1 } else { 0 },
+                tspins: self.stats.tspins + if tspin == Some(TSpin::Full) { 1 } else { 0 },
+                mini_tspins: self.stats.mini_tspins
+                    + if tspin == Some(TSpin::Mini) { 1 } else { 0 },
+                combo,
+                max_combo: self.stats.max_combo.max(combo),
+                back_to_back,
+                perfect_clears: self.stats.perfect_clears + if perfect_clear { 1 } else { 0 },
             },
             game_over: false,
             delta: Some(Delta {
                 r#move,
                 eroded,
+                tspin,
+                perfect_clear,
+                placement,
                 #[cfg(test)]
                 cleared: cleared_rows,
             }),
+            queue: self.queue,
+            hold: self.hold,
+            scoring: self.scoring,
         }
     }
 
@@ -121,8 +226,11 @@ impl State {
     pub fn js_value(&self) -> JsValue {
         serde_wasm_bindgen::to_value(&WasmState {
             board: self.board.get_raw_data(),
+            size: self.board.size(),
             stats: self.stats(),
             game_over: self.game_over,
+            next: self.queue.pieces(),
+            hold: self.hold,
         })
         .unwrap()
     }
@@ -133,3 +241,39 @@ impl State {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::r#move::Position;
+    use std::str::FromStr;
+
+    fn place_i(rows: &[&str]) -> State {
+        let mut lines = vec![".........."; 20 - rows.len()];
+        lines.extend(rows);
+        let state = State::new(Board::from_str(&lines.join("\n")).unwrap());
+        // a horizontal I in the right four columns of the bottom row
+        let r#move = Move {
+            piece: Piece::from_name('I').unwrap(),
+            pos: Position {
+                rot: 0,
+                row: 17,
+                col: 6,
+            },
+        };
+        state.future(r#move, None)
+    }
+
+    #[test]
+    fn test_perfect_clear() {
+        let state = place_i(&["######...."]);
+        assert!(state.board().is_empty());
+        assert!(state.delta().unwrap().perfect_clear);
+        assert_eq!(state.stats().perfect_clears, 1);
+
+        let state = place_i(&["#.........", "######...."]);
+        assert_eq!(state.stats().lines, 1);
+        assert!(!state.delta().unwrap().perfect_clear);
+        assert_eq!(state.stats().perfect_clears, 0);
+    }
+}
@@ -1,5 +1,5 @@
 use crate::board::Board;
-use crate::board::{BOARD_HEIGHT, BOARD_WIDTH};
+use crate::board::{BOARD_HEIGHT, BOARD_WIDTH, BoardSize};
 use crate::r#move::{Move, Position, move_dijkstra};
 use crate::piece::Piece;
 use crate::state::State;
@@ -36,7 +36,12 @@ pub fn random_board() -> Board {
 
 /// Generates a random state by dropping pieces until no more moves are possible.
 pub fn random_state() -> State {
-    let mut state = State::new(Board::default());
+    random_state_with_size(BoardSize::default())
+}
+
+/// Generates a random state on a board of the given size.
+pub fn random_state_with_size(size: BoardSize) -> State {
+    let mut state = State::new(Board::new(size));
     let mut rng = rand::rng();
     loop {
         let piece = Piece::from_index(rng.random_range(0..7));
@@ -48,7 +53,7 @@ pub fn random_state() -> State {
             break;
         }
         let pos = possible_positions[rng.random_range(0..possible_positions.len())];
-        state = state.future(Move { piece, pos });
+        state = state.future(Move { piece, pos }, None);
     }
     state
 }
@@ -75,10 +80,16 @@ pub fn run_py_feature(state: &State, feature_name: &str) -> usize {
                 .set_item("piece_idx", delta.r#move.piece.index())
                 .unwrap();
             delta_dict.set_item("rot", delta.r#move.pos.rot).unwrap();
-            delta_dict.set_item("col", delta.r#move.pos.col).unwrap();
-            delta_dict.set_item("row", delta.r#move.pos.row).unwrap();
+            // the Python shapes only contain the filled cells
+            let bounds = delta.r#move.pattern().bounds();
+            delta_dict
+                .set_item("col", delta.r#move.pos.col + bounds.left as isize)
+                .unwrap();
+            delta_dict
+                .set_item("row", delta.r#move.pos.row + bounds.top as isize)
+                .unwrap();
             delta_dict
-                .set_item("cleared", delta.cleared.clone())
+                .set_item("cleared", delta.cleared.iter().collect::<Vec<_>>())
                 .unwrap();
         }
 
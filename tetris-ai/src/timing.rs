@@ -0,0 +1,651 @@
+use crate::{
+    board::Board,
+    r#move::{Move, Position},
+    piece::{MAX_PATTERN_SIZE, MAX_ROTATIONS, Piece},
+};
+use std::collections::{HashMap, hash_map::Entry};
+use std::hash::{BuildHasherDefault, Hasher};
+use std::mem::take;
+use std::str::FromStr;
+
+/// How the time it takes to move a piece is modelled when generating moves.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Timing {
+    /// Approximates time pressure by limiting the number of moves per row the piece falls.
+    #[default]
+    Ticks,
+    /// Simulates every frame of NES Tetris, see [`NesTiming`].
+    Nes(NesTiming),
+}
+
+/// The frame-exact timing of NES Tetris, running at 60 frames per second.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NesTiming {
+    pub shift: ShiftModel,
+    /// The number of frames before the next piece spawns, during which the player can charge DAS.
+    /// This is the shortest delay, after locking a piece in the bottom two rows,
+    /// see [`NesTiming::entry_delay_after`].
+    pub entry_delay: u64,
+}
+
+impl Default for NesTiming {
+    fn default() -> Self {
+        Self {
+            shift: ShiftModel::Das {
+                charge: 16,
+                repeat: 6,
+            },
+            entry_delay: 10,
+        }
+    }
+}
+
+/// How fast the player can shift a piece to the left or right.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShiftModel {
+    /// Delayed auto shift: pressing a direction shifts the piece once, holding it shifts the piece
+    /// again after `charge` frames and then every `repeat` frames.
+    /// Pushing against a wall fully charges DAS. The player does not tap faster than DAS.
+    Das { charge: u64, repeat: u64 },
+    /// Hypertapping: the player presses a direction every `frames` frames.
+    Tap { frames: u64 },
+}
+
+/// The number of frames it takes a piece to fall one row at each level, up to level 29.
+const FRAMES_PER_ROW: [u64; 30] = [
+    48, 43, 38, 33, 28, 23, 18, 13, 8, 6, // levels 0 to 9
+    5, 5, 5, 4, 4, 4, 3, 3, 3, // levels 10 to 18
+    2, 2, 2, 2, 2, 2, 2, 2, 2, 2, // levels 19 to 28
+    1, // level 29 and higher
+];
+
+/// The entry delay is longer by 2 frames for every 4 rows higher that a piece locks, up to 4 times.
+const ENTRY_DELAY_ROWS: u64 = 4;
+const ENTRY_DELAY_STEP: u64 = 2;
+const ENTRY_DELAY_STEPS: u64 = 4;
+
+impl NesTiming {
+    /// Returns the number of frames it takes a piece to fall one row at the given level.
+    pub fn frames_per_row(level: u64) -> u64 {
+        FRAMES_PER_ROW[(level as usize).min(FRAMES_PER_ROW.len() - 1)]
+    }
+
+    /// Returns the number of frames before the next piece spawns after a piece locks,
+    /// which depends on the row of the center of the piece, from 10 to 18 frames by default.
+    pub fn entry_delay_after(&self, r#move: Move, board_height: usize) -> u64 {
+        let piece = r#move.piece;
+        let center = r#move.pos.row + piece.rotation_system().spawn_offset(piece.index()).0;
+        // the number of rows below the center, the bottom two rows have the shortest delay
+        let height = (board_height as isize - 1 - center).max(0) as u64;
+        let steps = ((height + ENTRY_DELAY_ROWS / 2) / ENTRY_DELAY_ROWS).min(ENTRY_DELAY_STEPS);
+        self.entry_delay + ENTRY_DELAY_STEP * steps
+    }
+}
+
+/// Pressing and releasing a button takes at least two frames.
+const MIN_TAP_FRAMES: u64 = 2;
+
+impl FromStr for Timing {
+    type Err = ();
+
+    /// Parses `ticks`, `nes` for DAS or `nes-tap:<presses per second>` for hypertapping.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "ticks" => Ok(Timing::Ticks),
+            None if s == "nes" => Ok(Timing::Nes(NesTiming::default())),
+            Some(("nes-tap", rate)) => {
+                let rate: u64 = rate.parse().map_err(|_| ())?;
+                if rate == 0 || rate > 60 / MIN_TAP_FRAMES {
+                    return Err(());
+                }
+                Ok(Timing::Nes(NesTiming {
+                    shift: ShiftModel::Tap {
+                        frames: (60 + rate / 2) / rate,
+                    },
+                    ..Default::default()
+                }))
+            }
+            _ => Err(()),
+        }
+    }
+}
+
+/// The state of a piece and the controller at the start of a frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct FrameState {
+    pos: Position,
+    /// The direction that is held, -1 for left, 1 for right or 0 for none.
+    dir: i8,
+    /// The number of frames until the piece can be shifted again.
+    cooldown: u64,
+    /// The number of frames since the piece last fell.
+    fall: u64,
+}
+
+/// The positions of the piece after each action during a frame that moved it.
+#[derive(Clone, Copy, Debug, Default)]
+struct Steps {
+    positions: [Position; 3],
+    len: usize,
+}
+
+impl Steps {
+    fn push(mut self, pos: Position) -> Self {
+        self.positions[self.len] = pos;
+        self.len += 1;
+        self
+    }
+
+    /// Returns the positions during the frame, or the unmoved position if nothing happened.
+    fn tick(&self, pos: Position) -> Vec<Position> {
+        if self.len == 0 {
+            vec![pos]
+        } else {
+            self.positions[..self.len].to_vec()
+        }
+    }
+}
+
+/// What can happen to the piece during a frame.
+enum Outcome {
+    Moved(FrameState),
+    Locked(Position),
+}
+
+/// The cooldowns and fall progress cannot be longer than this, so that states can be packed into a key.
+const MAX_FRAMES: u64 = 63;
+const UNVISITED: u8 = u8::MAX;
+
+/// Finds the landing positions of a piece by simulating every frame with the NES timing.
+/// Only the earliest way to reach each state is kept, as the piece falls at the same rate afterwards.
+/// A state is also skipped if the same position and direction was reached with less fall progress
+/// and a lower cooldown, as the player can then do everything the same or sooner.
+/// Pieces that just fell cannot be soft dropped in the same frame, so they are compared separately.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct FrameSearch {
+    /// For each position, held direction and cooldown, the lowest fall progress that was reached
+    /// with the same or a lower cooldown.
+    falls: Vec<u8>,
+    /// For each position and held direction, the lowest cooldown of a piece that just fell.
+    fallen: Vec<u8>,
+    /// The number of different cooldowns.
+    cooldowns: usize,
+    /// The state each state was first reached from.
+    parents: HashMap<u32, FrameState, BuildHasherDefault<KeyHasher>>,
+    /// The state before the frame in which the piece locked, and the number of frames since the
+    /// piece spawned.
+    locks: HashMap<Position, (FrameState, u64)>,
+    frontier: Vec<FrameState>,
+    next: Vec<FrameState>,
+    shifts: Vec<(Position, i8, u64, Steps)>,
+    rotations: Vec<(Position, Steps)>,
+    /// The board and rules of the last search, used to recompute the steps of a path.
+    board: Board,
+    rules: Option<FrameRules>,
+    index_rows: usize,
+    index_cols: usize,
+}
+
+/// The rules that decide what can happen during a frame.
+#[derive(Clone, Copy, Debug)]
+struct FrameRules {
+    piece: Piece,
+    timing: NesTiming,
+    rotate_180: bool,
+    frames_per_row: u64,
+}
+
+impl FrameSearch {
+    /// Simulates the piece from the start position, and pushes the landing positions in the
+    /// order they are reached.
+    pub(crate) fn generate(
+        &mut self,
+        board: &Board,
+        start: Move,
+        level: u64,
+        timing: &NesTiming,
+        rotate_180: bool,
+        destinations: &mut Vec<Position>,
+    ) {
+        let size = board.size();
+        self.index_rows = size.height + MAX_PATTERN_SIZE;
+        self.index_cols = size.width + 2 * MAX_PATTERN_SIZE;
+        let rules = FrameRules {
+            piece: start.piece,
+            timing: *timing,
+            rotate_180,
+            frames_per_row: NesTiming::frames_per_row(level),
+        };
+        self.rules = Some(rules);
+        self.board = *board;
+        let max_cooldown = match timing.shift {
+            ShiftModel::Das { charge, repeat } => charge.max(repeat),
+            ShiftModel::Tap { frames } => frames,
+        };
+        assert!(max_cooldown <= MAX_FRAMES, "shift delays are too long");
+        self.cooldowns = max_cooldown as usize + 1;
+        let slots = MAX_ROTATIONS * self.index_rows * self.index_cols * 3;
+        self.falls.clear();
+        self.falls.resize(slots * self.cooldowns, UNVISITED);
+        self.fallen.clear();
+        self.fallen.resize(slots, UNVISITED);
+        self.parents.clear();
+        self.locks.clear();
+        self.frontier.clear();
+        if board.overlaps_move(start) {
+            return;
+        }
+        let spawn = FrameState {
+            pos: start.pos,
+            dir: 0,
+            cooldown: 0,
+            fall: 0,
+        };
+        self.frontier.push(spawn);
+        if let ShiftModel::Das { charge, .. } = timing.shift
+            && timing.entry_delay >= charge
+        {
+            // DAS was charged while waiting for the piece to spawn, as it is not known
+            // where the previous piece locked, the shortest delay is assumed
+            self.frontier.push(FrameState { dir: -1, ..spawn });
+            self.frontier.push(FrameState { dir: 1, ..spawn });
+        }
+        for i in 0..self.frontier.len() {
+            self.dominate(self.frontier[i]);
+        }
+
+        let mut frame = 0;
+        let (mut shifts, mut rotations) = (take(&mut self.shifts), take(&mut self.rotations));
+        while !self.frontier.is_empty() {
+            frame += 1;
+            self.next.clear();
+            for i in 0..self.frontier.len() {
+                let state = self.frontier[i];
+                rules.advance(
+                    board,
+                    state,
+                    &mut shifts,
+                    &mut rotations,
+                    |outcome, _| match outcome {
+                        Outcome::Moved(next) => self.visit(state, next),
+                        Outcome::Locked(pos) => {
+                            if let Entry::Vacant(entry) = self.locks.entry(pos) {
+                                entry.insert((state, frame));
+                                destinations.push(pos);
+                            }
+                        }
+                    },
+                );
+            }
+            std::mem::swap(&mut self.frontier, &mut self.next);
+        }
+        (self.shifts, self.rotations) = (shifts, rotations);
+    }
+
+    /// Returns the index of the position and held direction of a state.
+    #[inline]
+    fn slot(&self, state: &FrameState) -> usize {
+        let row = (state.pos.row + MAX_PATTERN_SIZE as isize) as usize;
+        let col = (state.pos.col + MAX_PATTERN_SIZE as isize) as usize;
+        let pos = (state.pos.rot * self.index_rows + row) * self.index_cols + col;
+        pos * 3 + (state.dir + 1) as usize
+    }
+
+    /// Packs a state into a key.
+    #[inline]
+    fn key(&self, state: &FrameState) -> u32 {
+        let frames = MAX_FRAMES + 1;
+        ((self.slot(state) as u64 * frames + state.fall) * frames + state.cooldown) as u32
+    }
+
+    /// Returns true if a state with the same position and direction, less fall progress and
+    /// a lower cooldown was already found.
+    #[inline]
+    fn dominated(&self, state: &FrameState) -> bool {
+        if state.fall == 0 {
+            return self.fallen[self.slot(state)] as u64 <= state.cooldown;
+        }
+        let index = self.slot(state) * self.cooldowns + state.cooldown as usize;
+        self.falls[index] as u64 <= state.fall
+    }
+
+    fn dominate(&mut self, state: FrameState) {
+        if state.fall == 0 {
+            let slot = self.slot(&state);
+            self.fallen[slot] = self.fallen[slot].min(state.cooldown as u8);
+            return;
+        }
+        let start = self.slot(&state) * self.cooldowns;
+        for fall in &mut self.falls[start + state.cooldown as usize..start + self.cooldowns] {
+            *fall = (*fall).min(state.fall as u8);
+        }
+    }
+
+    /// Adds a state to the next frame, unless it is dominated by a state that was found before.
+    fn visit(&mut self, parent: FrameState, state: FrameState) {
+        if self.dominated(&state) {
+            return;
+        }
+        self.dominate(state);
+        let key = self.key(&state);
+        self.parents.insert(key, parent);
+        self.next.push(state);
+    }
+
+    /// Returns the positions of the piece in every frame until it locks at the destination,
+    /// starting with the spawn position.
+    pub(crate) fn ticks(&self, destination: Position) -> Vec<Vec<Position>> {
+        let (mut state, _) = self.locks[&destination];
+        let mut ticks = vec![
+            self.steps(state, Outcome::Locked(destination))
+                .tick(state.pos),
+        ];
+        while let Some(&parent) = self.parents.get(&self.key(&state)) {
+            ticks.push(self.steps(parent, Outcome::Moved(state)).tick(parent.pos));
+            state = parent;
+        }
+        ticks.push(vec![state.pos]);
+        ticks.reverse();
+        ticks
+    }
+
+    /// Returns the position the piece was rotated from, if the last action that moved the piece
+    /// before it locked at the destination was a rotation.
+    pub(crate) fn rotated_from(&self, destination: Position) -> Option<Position> {
+        let (mut state, _) = self.locks[&destination];
+        let mut steps = self.steps(state, Outcome::Locked(destination));
+        // find the last frame in which the piece moved
+        while steps.len == 0 {
+            let &parent = self.parents.get(&self.key(&state))?;
+            steps = self.steps(parent, Outcome::Moved(state));
+            state = parent;
+        }
+        let last = steps.positions[steps.len - 1];
+        let previous = match steps.len {
+            1 => state.pos,
+            len => steps.positions[len - 2],
+        };
+        (previous.rot != last.rot).then_some(previous)
+    }
+
+    /// Finds the steps during a frame that lead from a state to the outcome.
+    fn steps(&self, from: FrameState, to: Outcome) -> Steps {
+        let rules = self.rules.expect("no moves generated");
+        let mut found = None;
+        let (mut shifts, mut rotations) = (Vec::new(), Vec::new());
+        rules.advance(
+            &self.board,
+            from,
+            &mut shifts,
+            &mut rotations,
+            |outcome, steps| {
+                let matches = match (&outcome, &to) {
+                    (Outcome::Moved(a), Outcome::Moved(b)) => a == b,
+                    (Outcome::Locked(a), Outcome::Locked(b)) => a == b,
+                    _ => false,
+                };
+                if matches && found.is_none() {
+                    found = Some(steps);
+                }
+            },
+        );
+        found.expect("state is not reachable from its parent")
+    }
+
+    /// Returns the number of frames from spawning the piece until it locks at the destination.
+    pub(crate) fn frames(&self, destination: Position) -> u64 {
+        self.locks[&destination].1
+    }
+}
+
+impl FrameRules {
+    /// Simulates a single frame from a state, calling `f` for every way it can end.
+    fn advance(
+        &self,
+        board: &Board,
+        state: FrameState,
+        shifts: &mut Vec<(Position, i8, u64, Steps)>,
+        rotations: &mut Vec<(Position, Steps)>,
+        mut f: impl FnMut(Outcome, Steps),
+    ) {
+        let piece = self.piece;
+        let gravity = state.fall + 1 >= self.frames_per_row;
+        let soft_drop = state.fall >= 1;
+        shift_options(shifts, board, piece, state, &self.timing);
+        for &(pos, dir, cooldown, steps) in shifts.iter() {
+            let cooldown = cooldown.saturating_sub(1);
+            rotation_options(
+                rotations,
+                board,
+                Move { piece, pos },
+                steps,
+                self.rotate_180,
+            );
+            for &(pos, steps) in rotations.iter() {
+                if !gravity {
+                    let waited = FrameState {
+                        pos,
+                        dir,
+                        cooldown,
+                        fall: state.fall + 1,
+                    };
+                    f(Outcome::Moved(waited), steps);
+                }
+                if gravity || soft_drop {
+                    match (Move { piece, pos }).drop(board) {
+                        Some(fallen) => {
+                            let dropped = FrameState {
+                                pos: fallen.pos,
+                                dir,
+                                cooldown,
+                                fall: 0,
+                            };
+                            f(Outcome::Moved(dropped), steps.push(fallen.pos));
+                        }
+                        None => f(Outcome::Locked(pos), steps),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Hashes the packed keys of the states, which are already well distributed.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct KeyHasher(u64);
+
+impl Hasher for KeyHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.write_u64(byte as u64);
+        }
+    }
+
+    #[inline]
+    fn write_u32(&mut self, n: u32) {
+        self.write_u64(n as u64);
+    }
+
+    #[inline]
+    fn write_u64(&mut self, n: u64) {
+        self.0 = (self.0 ^ n).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Finds the ways the player can shift the piece during a frame.
+fn shift_options(
+    options: &mut Vec<(Position, i8, u64, Steps)>,
+    board: &Board,
+    piece: Piece,
+    state: FrameState,
+    timing: &NesTiming,
+) {
+    options.clear();
+    let shift = |dir: i8| {
+        let pos = Position {
+            col: state.pos.col + dir as isize,
+            ..state.pos
+        };
+        Move { piece, pos }.is_valid(board).then_some(pos)
+    };
+    match timing.shift {
+        ShiftModel::Das { charge, repeat } => {
+            let press = |dir: i8| match shift(dir) {
+                Some(pos) => (pos, dir, charge, Steps::default().push(pos)),
+                None => (state.pos, dir, 0, Steps::default()), // charged against the wall
+            };
+            // the player does not tap faster than DAS, so pressing a direction again
+            // only shifts the piece once the delay since the last shift has passed
+            let released = (state.pos, 0, state.cooldown, Steps::default());
+            if state.dir == 0 {
+                options.push(released);
+                if state.cooldown == 0 {
+                    options.push(press(-1));
+                    options.push(press(1));
+                }
+            } else {
+                // keep holding the direction, which shifts the piece once DAS is charged
+                options.push(match shift(state.dir) {
+                    Some(pos) if state.cooldown == 0 => {
+                        (pos, state.dir, repeat, Steps::default().push(pos))
+                    }
+                    Some(_) => (state.pos, state.dir, state.cooldown, Steps::default()),
+                    None => (state.pos, state.dir, 0, Steps::default()),
+                });
+                options.push(released);
+                if state.cooldown == 0 {
+                    options.push(press(-state.dir));
+                }
+            }
+        }
+        ShiftModel::Tap { frames } => {
+            options.push((state.pos, 0, state.cooldown, Steps::default()));
+            if state.cooldown == 0 {
+                for dir in [-1, 1] {
+                    if let Some(pos) = shift(dir) {
+                        options.push((pos, 0, frames, Steps::default().push(pos)));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Finds the ways the player can rotate the piece during a frame, after shifting it.
+fn rotation_options(
+    options: &mut Vec<(Position, Steps)>,
+    board: &Board,
+    r#move: Move,
+    steps: Steps,
+    rotate_180: bool,
+) {
+    options.clear();
+    options.push((r#move.pos, steps));
+    let rotations = r#move.piece.num_rotations();
+    // clockwise, counter-clockwise and 180 degrees, if the piece has that many rotations
+    let turns = match rotations {
+        1 => &[][..],
+        2 => &[1],
+        _ if rotate_180 => &[1, 3, 2],
+        _ => &[1, 3],
+    };
+    for &turn in turns {
+        if let Some(rotated) = r#move.rotate(board, (r#move.pos.rot + turn) % rotations) {
+            options.push((rotated.pos, steps.push(rotated.pos)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        r#move::MoveGenerator,
+        piece::{N_PIECES, Piece},
+        test::random_board,
+    };
+
+    fn min_col(generator: &MoveGenerator) -> isize {
+        generator
+            .destinations()
+            .iter()
+            .map(|pos| pos.col)
+            .min()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse_timing() {
+        assert_eq!("ticks".parse(), Ok(Timing::Ticks));
+        assert_eq!("nes".parse(), Ok(Timing::Nes(NesTiming::default())));
+        let Ok(Timing::Nes(timing)) = "nes-tap:15".parse() else {
+            panic!("expected a NES timing");
+        };
+        assert_eq!(timing.shift, ShiftModel::Tap { frames: 4 });
+        assert!("nes-tap:31".parse::<Timing>().is_err());
+        assert!("nes-tap:0".parse::<Timing>().is_err());
+    }
+
+    #[test]
+    fn test_entry_delay() {
+        let timing = NesTiming::default();
+        let t = |row| Move {
+            piece: Piece::from_name('T').unwrap(),
+            pos: Position {
+                rot: 0,
+                row,
+                col: 4,
+            },
+        };
+        // the center of the T is in the second row of its pattern
+        let delays = [18, 17, 16, 15, 14].map(|row| timing.entry_delay_after(t(row), 20));
+        assert_eq!(delays, [10, 10, 12, 12, 12]);
+        assert_eq!(timing.entry_delay_after(t(6), 20), 16);
+        assert_eq!(timing.entry_delay_after(t(4), 20), 18);
+        assert_eq!(timing.entry_delay_after(t(-1), 20), 18);
+    }
+
+    #[test]
+    fn test_gravity_limits_reach() {
+        let board = Board::default();
+        let piece = Piece::from_name('O').unwrap();
+        let mut generator = MoveGenerator::new();
+        generator.set_timing(Timing::Nes(NesTiming::default()));
+        generator.generate(&board, piece, Some(0));
+        assert_eq!(min_col(&generator), 0);
+        // a piece falls 18 rows in 18 frames, DAS only shifts it at frames 1 and 17
+        generator.generate(&board, piece, Some(29));
+        assert_eq!(min_col(&generator), 2);
+        // tapping 30 times per second shifts it every other frame
+        generator.set_timing("nes-tap:30".parse().unwrap());
+        generator.generate(&board, piece, Some(29));
+        assert_eq!(min_col(&generator), 0);
+    }
+
+    #[test]
+    fn test_frame_paths() {
+        let mut generator = MoveGenerator::new();
+        generator.set_timing(Timing::Nes(NesTiming::default()));
+        for _ in 0..10 {
+            let board = random_board();
+            for piece in (0..N_PIECES).map(Piece::from_index) {
+                generator.generate(&board, piece, Some(18));
+                for &destination in generator.destinations() {
+                    let path = generator.path(destination);
+                    assert_eq!(path.final_move().pos, destination);
+                    assert!(path.final_move().is_valid(&board));
+                    assert_eq!(path.rotated_from(), generator.rotated_from(destination));
+                    // a tick for the spawn and one for every frame until the piece locks
+                    let entry_delay = NesTiming::default()
+                        .entry_delay_after(path.final_move(), board.size().height);
+                    let frames = path.frames().unwrap() - entry_delay;
+                    assert_eq!(path.into_moves().len() as u64, frames + 1);
+                }
+            }
+        }
+    }
+}
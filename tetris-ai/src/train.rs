@@ -1,9 +1,15 @@
 use std::str::FromStr;
 
 use crate::{
-    feature::{Features, Weights},
+    board::BoardSize,
+    feature::Features,
+    rng::{GameRng, RandomizerKind, seeded_rng},
+    rotation::{Nes, RotationSystem, RotationSystemKind},
+    scoring::ScoringKind,
     simulator::Simulator,
+    timing::Timing,
 };
+use rand::Rng;
 use rand_distr::{Distribution, Normal};
 use serde::Serialize;
 #[cfg(feature = "wasm")]
@@ -21,6 +27,14 @@ pub struct Trainer {
     weights: Vec<f64>,
     st_dev: Vec<f64>,
     criterion: TrainCriterion,
+    seed: Option<u64>,
+    rng: GameRng,
+    randomizer: RandomizerKind,
+    board_size: BoardSize,
+    rotation_system: &'static dyn RotationSystem,
+    scoring: ScoringKind,
+    timing: Timing,
+    rotate_180: bool,
     // state
     current_gen: Option<Vec<Vec<f64>>>,
     current_gen_index: usize,
@@ -29,18 +43,52 @@ pub struct Trainer {
 }
 
 impl Trainer {
-    pub fn new(features: Features, criterion: TrainCriterion) -> Self {
+    /// Creates a new trainer, if a seed is given the whole training run is reproducible.
+    pub fn new(features: Features, criterion: TrainCriterion, seed: Option<u64>) -> Self {
         Self {
             weights: vec![0.0; features.len()],
             st_dev: vec![WEIGHT_RANGE; features.len()],
             features,
             criterion,
+            seed,
+            rng: seeded_rng(seed),
+            randomizer: RandomizerKind::default(),
+            board_size: BoardSize::default(),
+            rotation_system: &Nes,
+            scoring: ScoringKind::default(),
+            timing: Timing::default(),
+            rotate_180: false,
             current_gen: None,
             current_gen_index: 1,
             current_results: Vec::with_capacity(MODELS_PER_GEN),
             current_model_index: 0,
         }
     }
+
+    /// Sets the randomizer used by the games that evaluate the models.
+    pub fn set_randomizer(&mut self, randomizer: RandomizerKind) {
+        self.randomizer = randomizer;
+    }
+
+    /// Sets the size of the board used by the games that evaluate the models.
+    pub fn set_board_size(&mut self, board_size: BoardSize) {
+        self.board_size = board_size;
+    }
+
+    /// Sets the rotation system used by the games that evaluate the models.
+    pub fn set_rotation_system(&mut self, rotation_system: &'static dyn RotationSystem) {
+        self.rotation_system = rotation_system;
+    }
+
+    /// Sets how points are awarded in the games that evaluate the models.
+    pub fn set_scoring(&mut self, scoring: ScoringKind) {
+        self.scoring = scoring;
+    }
+
+    /// Sets how the time it takes to move a piece is modelled in the games that evaluate the models.
+    pub fn set_timing(&mut self, timing: Timing) {
+        self.timing = timing;
+    }
 }
 
 #[cfg(feature = "wasm")]
@@ -54,9 +102,17 @@ impl TrainState {
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl Trainer {
     #[cfg(feature = "wasm")]
-    pub fn from_feature_names(feature_names: Box<[String]>, criterion: String) -> Self {
+    pub fn from_feature_names(
+        feature_names: Vec<String>,
+        criterion: String,
+        seed: Option<u64>,
+    ) -> Self {
         let strs = feature_names.iter().map(String::as_str).collect::<Vec<_>>();
-        Self::new(Features::from_names(&strs), criterion.parse().unwrap())
+        Self::new(
+            Features::from_names(&strs),
+            criterion.parse().unwrap(),
+            seed,
+        )
     }
 
     pub fn reset(&mut self) {
@@ -66,10 +122,56 @@ impl Trainer {
         self.current_gen_index = 1;
         self.current_results.clear();
         self.current_model_index = 0;
+        self.rng = seeded_rng(self.seed);
+    }
+
+    /// Selects a randomizer by name, see [`RandomizerKind`] for the available names.
+    pub fn select_randomizer(&mut self, name: &str) {
+        let Ok(kind) = name.parse::<RandomizerKind>() else {
+            panic!("Unknown randomizer: '{}'", name);
+        };
+        self.set_randomizer(kind);
+    }
+
+    /// Selects a board size by name, e.g. `10x20` or `10x20+20`, see [`BoardSize`].
+    pub fn select_board_size(&mut self, name: &str) {
+        let Ok(board_size) = name.parse::<BoardSize>() else {
+            panic!("Unknown board size: '{}'", name);
+        };
+        self.set_board_size(board_size);
+    }
+
+    /// Selects a rotation system by name, see [`RotationSystemKind`] for the available names.
+    pub fn select_rotation_system(&mut self, name: &str) {
+        let Ok(kind) = name.parse::<RotationSystemKind>() else {
+            panic!("Unknown rotation system: '{}'", name);
+        };
+        self.set_rotation_system(kind.system());
+    }
+
+    /// Selects a scoring ruleset by name, see [`ScoringKind`] for the available names.
+    pub fn select_scoring(&mut self, name: &str) {
+        let Ok(scoring) = name.parse::<ScoringKind>() else {
+            panic!("Unknown scoring: '{}'", name);
+        };
+        self.set_scoring(scoring);
+    }
+
+    /// Selects how the time it takes to move a piece is modelled by name, see [`Timing`].
+    pub fn select_timing(&mut self, name: &str) {
+        let Ok(timing) = name.parse::<Timing>() else {
+            panic!("Unknown timing: '{}'", name);
+        };
+        self.set_timing(timing);
+    }
+
+    /// Allows the games that evaluate the models to rotate pieces by 180 degrees.
+    pub fn set_rotate_180(&mut self, enabled: bool) {
+        self.rotate_180 = enabled;
     }
 
     pub fn step(&mut self) -> TrainState {
-        let mut rng = rand::rng();
+        let rng = &mut self.rng;
         let generation = self.current_gen.get_or_insert_with(|| {
             // New generation
             (0..MODELS_PER_GEN)
@@ -79,7 +181,7 @@ impl Trainer {
                         .zip(self.st_dev.iter())
                         .map(|(value, std_dev)| {
                             let dist = Normal::new(*value, *std_dev).unwrap();
-                            dist.sample(&mut rng)
+                            dist.sample(rng)
                         })
                         .collect()
                 })
@@ -87,7 +189,18 @@ impl Trainer {
         });
         // Eval a single model inside the current generation
         let weights = generation[self.current_model_index].clone();
-        let score = self.criterion.eval(self.features.with_weights(&weights));
+        // every model plays its own reproducible game derived from the trainer's generator
+        let mut sim = Simulator::new_with_weights(
+            self.features.with_weights(&weights),
+            Some(self.rng.random()),
+        );
+        sim.set_randomizer(self.randomizer.build());
+        sim.set_board_size(self.board_size);
+        sim.set_rotation_system(self.rotation_system);
+        sim.set_scoring(self.scoring);
+        sim.set_timing(self.timing);
+        sim.set_rotate_180(self.rotate_180);
+        let score = self.criterion.eval(sim);
         let result = EvalResult { weights, score };
         self.current_results.push(result.clone());
         self.current_model_index += 1;
@@ -174,11 +287,11 @@ pub enum TrainCriterion {
     Score,
     Level,
     Tetrises,
+    PerfectClears,
 }
 
 impl TrainCriterion {
-    fn eval(&self, weights: Weights) -> f64 {
-        let mut sim = Simulator::new_with_weights(weights);
+    fn eval(&self, mut sim: Simulator) -> f64 {
         match self {
             TrainCriterion::Score => {
                 sim.run_for(EVAL_ITERATIONS);
@@ -196,6 +309,12 @@ impl TrainCriterion {
                 sim.run_for(EVAL_ITERATIONS);
                 sim.stats().score as f64 * 10.0 * (sim.stats().tetrises + 1) as f64
             }
+            TrainCriterion::PerfectClears => {
+                sim.run_for(EVAL_ITERATIONS);
+                // the cleared rows break ties between models with the same number of perfect clears
+                let lines = sim.stats().lines as f64 / (EVAL_ITERATIONS * 4) as f64;
+                sim.stats().perfect_clears as f64 + lines
+            }
         }
     }
 }
@@ -208,6 +327,7 @@ impl FromStr for TrainCriterion {
             "score" => Ok(TrainCriterion::Score),
             "level" => Ok(TrainCriterion::Level),
             "tetrises" => Ok(TrainCriterion::Tetrises),
+            "perfect_clears" => Ok(TrainCriterion::PerfectClears),
             _ => Err(()),
         }
     }
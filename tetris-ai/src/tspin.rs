@@ -0,0 +1,158 @@
+use crate::{
+    board::Board,
+    r#move::{Move, Position},
+    piece::{PIECE_NAMES, Pattern},
+};
+use serde::Serialize;
+
+/// A T piece that was rotated into a position where it is surrounded by filled cells.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum TSpin {
+    /// Only one of the corners on the pointing side of the T is filled.
+    Mini,
+    Full,
+}
+
+/// The diagonal corners around the center of the T, as (row, column) offsets.
+const CORNERS: [(isize, isize); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+
+impl TSpin {
+    /// Detects a T-spin using the 3-corner rule of the guideline games, before the move is imprinted.
+    /// `rotated_from` is the position the piece was rotated from, if the last action was a rotation.
+    pub fn detect(board: &Board, r#move: Move, rotated_from: Option<Position>) -> Option<TSpin> {
+        let from = rotated_from?;
+        if PIECE_NAMES[r#move.piece.index()] != 'T' {
+            return None;
+        }
+        let ((center_row, center_col), (front_row, front_col)) = t_center(r#move.pattern())?;
+        let filled = |&(row, col): &(isize, isize)| {
+            board.is_blocked(
+                r#move.pos.row + center_row + row,
+                r#move.pos.col + center_col + col,
+            )
+        };
+        if CORNERS.iter().filter(|corner| filled(corner)).count() < 3 {
+            return None;
+        }
+        let front_filled = CORNERS
+            .iter()
+            .filter(|&&(row, col)| row == front_row || col == front_col)
+            .filter(|corner| filled(corner))
+            .count();
+        // kicking the piece two rows and one column upgrades a mini, like the last kick of SRS,
+        // which is used by T-spin triples and fins, the kicks straight up or down do not
+        let far_kick =
+            (r#move.pos.row - from.row).abs() == 2 && (r#move.pos.col - from.col).abs() == 1;
+        if front_filled == 2 || far_kick {
+            Some(TSpin::Full)
+        } else {
+            Some(TSpin::Mini)
+        }
+    }
+}
+
+/// Returns the center cell of a T pattern and the direction it points to, as (row, column) offsets.
+/// This does not depend on the rotation system, as the shapes of the T differ between them.
+fn t_center(pattern: Pattern) -> Option<((isize, isize), (isize, isize))> {
+    let filled =
+        |row: isize, col: isize| row >= 0 && col >= 0 && pattern.filled(row as usize, col as usize);
+    for row in 0..pattern.rows() as isize {
+        for col in 0..pattern.cols() as isize {
+            if !filled(row, col) {
+                continue;
+            }
+            let neighbours = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+            let mut missing = neighbours
+                .iter()
+                .filter(|&&(dr, dc)| !filled(row + dr, col + dc));
+            // the center is the only cell with three neighbours, it points away from the missing one
+            if let (Some(&(dr, dc)), None) = (missing.next(), missing.next()) {
+                return Some(((row, col), (-dr, -dc)));
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{piece::Piece, rotation::Srs};
+    use std::str::FromStr;
+
+    fn t_move(rot: usize, row: isize, col: isize) -> Move {
+        Move {
+            piece: Piece::from_name('T').unwrap().with_rotation_system(&Srs),
+            pos: Position { rot, row, col },
+        }
+    }
+
+    #[test]
+    fn test_tspin_double() {
+        let mut rows = vec![".........."; 17];
+        rows.extend(["##........", "#...######", "##.#######"]);
+        let board = Board::from_str(&rows.join("\n")).unwrap();
+        // pointing down into the slot
+        let r#move = t_move(2, 17, 1);
+        assert!(r#move.is_valid(&board));
+        let from = Some(Position {
+            rot: 1,
+            ..r#move.pos
+        });
+        assert_eq!(TSpin::detect(&board, r#move, from), Some(TSpin::Full));
+        // the last action was not a rotation
+        assert_eq!(TSpin::detect(&board, r#move, None), None);
+    }
+
+    #[test]
+    fn test_tspin_mini() {
+        let mut rows = vec![".........."; 17];
+        rows.extend(["..#.......", "..########", "#.########"]);
+        let board = Board::from_str(&rows.join("\n")).unwrap();
+        // pointing left, only one of the corners on the left is filled
+        let r#move = t_move(3, 17, 0);
+        assert!(r#move.is_valid(&board));
+        let from = Some(Position {
+            rot: 0,
+            ..r#move.pos
+        });
+        assert_eq!(TSpin::detect(&board, r#move, from), Some(TSpin::Mini));
+    }
+
+    #[test]
+    fn test_vertical_kick() {
+        let mut rows = vec![".........."; 15];
+        rows.extend([
+            "...#......",
+            "..........",
+            "..#.......",
+            "..........",
+            "..#.#.....",
+        ]);
+        let board = Board::from_str(&rows.join("\n")).unwrap();
+        // rotating from pointing down to pointing right is only possible with the (2, 0) kick
+        let from = t_move(2, 15, 2);
+        assert!(from.is_valid(&board));
+        let r#move = from.rotate(&board, 1).unwrap();
+        assert_eq!(r#move.pos, t_move(1, 17, 2).pos);
+        // only one of the corners on the right is filled
+        assert_eq!(
+            TSpin::detect(&board, r#move, Some(from.pos)),
+            Some(TSpin::Mini)
+        );
+        // the last kick also moves the piece one column, which upgrades the mini
+        let from = Position { col: 3, ..from.pos };
+        assert_eq!(TSpin::detect(&board, r#move, Some(from)), Some(TSpin::Full));
+    }
+
+    #[test]
+    fn test_no_tspin() {
+        let board = Board::default();
+        let r#move = t_move(2, 17, 3);
+        let from = Some(Position {
+            rot: 1,
+            ..r#move.pos
+        });
+        assert_eq!(TSpin::detect(&board, r#move, from), None);
+    }
+}